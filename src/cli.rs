@@ -1,6 +1,7 @@
+use std::path::PathBuf;
 use std::str::FromStr;
 use crate::commit_reference::CommitReference;
-use clap::{command, Parser, Subcommand, ValueEnum};
+use clap::{Parser, Subcommand, ValueEnum};
 
 #[derive(Parser)]
 #[command(name = "gitai")]
@@ -15,15 +16,67 @@ pub struct Cli {
     #[arg(short, long)]
     pub api_key: Option<String>,
 
+    /// Override the provider's API base URL, the root the endpoint path such as
+    /// `/chat/completions` is appended to
+    #[arg(long)]
+    pub base_url: Option<String>,
+
+    /// Extra header sent with every request, can be repeated
+    #[arg(short = 'H', long = "header", value_name = "NAME: VALUE")]
+    pub headers: Vec<String>,
+
+    /// Always send the request, without reading or writing the response cache
+    #[arg(long)]
+    pub no_cache: bool,
+
+    /// Print the prompt and its estimated cost instead of sending it
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Add every request and its response to a fixture file, for --replay
+    #[arg(long, value_name = "FILE", conflicts_with = "replay")]
+    pub record: Option<PathBuf>,
+
+    /// Answer from a fixture file made with --record instead of a provider
+    #[arg(long, value_name = "FILE")]
+    pub replay: Option<PathBuf>,
+
+    /// Answer with a shell command reading the messages as JSON on stdin, instead of a provider
+    #[arg(long, value_name = "COMMAND", conflicts_with = "replay")]
+    pub exec: Option<String>,
+
     #[command(subcommand)]
     pub command: Commands,
 }
-#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, ValueEnum, Debug)]
 pub enum LLMProviderType {
     Openai,
     Phind,
     Anthropic,
-    Grok
+    Grok,
+    Ollama,
+    OpenaiCompatible,
+    Gemini,
+    Azure,
+    Replay,
+    Exec,
+}
+
+impl LLMProviderType {
+    /// The model a provider uses when none is configured, empty for the providers without one
+    pub fn default_model(&self) -> &'static str {
+        match self {
+            LLMProviderType::Openai => "gpt-4o-mini",
+            LLMProviderType::Phind => "Phind-70B",
+            LLMProviderType::Anthropic => "claude-3-5-sonnet-latest",
+            LLMProviderType::Grok => "llama-3.3-70b-versatile",
+            LLMProviderType::Ollama => "llama3.2",
+            LLMProviderType::OpenaiCompatible => "gpt-4o-mini",
+            LLMProviderType::Gemini => "gemini-2.0-flash",
+            LLMProviderType::Azure => "gpt-4o-mini",
+            LLMProviderType::Replay | LLMProviderType::Exec => "",
+        }
+    }
 }
 
 impl FromStr for LLMProviderType {
@@ -35,6 +88,12 @@ impl FromStr for LLMProviderType {
             "phind" => Ok(LLMProviderType::Phind),
             "anthropic" => Ok(LLMProviderType::Anthropic),
             "grok" => Ok(LLMProviderType::Grok),
+            "ollama" => Ok(LLMProviderType::Ollama),
+            "openai-compatible" => Ok(LLMProviderType::OpenaiCompatible),
+            "gemini" => Ok(LLMProviderType::Gemini),
+            "azure" => Ok(LLMProviderType::Azure),
+            "replay" => Ok(LLMProviderType::Replay),
+            "exec" => Ok(LLMProviderType::Exec),
             _ => Err("Invalid provider".to_string()),
         }
     }
@@ -55,4 +114,124 @@ pub enum Commands {
         staged: bool,
     },
     Configure,
+    /// Suggest branch names from a description or from current changes
+    Branch {
+        #[arg(group = "source")]
+        description: Option<String>,
+
+        #[arg(long, group = "source")]
+        from_diff: bool,
+
+        /// use staged changes with --from-diff
+        #[arg(long, requires = "from_diff")]
+        staged: bool,
+
+        /// ticket id to fill the <ticket> placeholder of the pattern
+        #[arg(short, long)]
+        ticket: Option<String>,
+
+        /// number of names to suggest
+        #[arg(short = 'n', long, default_value_t = 3)]
+        count: usize,
+    },
+    /// Regenerate the messages of the commits in a range ending at HEAD
+    Reword {
+        /// `<from>..HEAD`, or `<from>` as a shorthand for it
+        #[arg(value_parser = clap::value_parser!(CommitReference))]
+        reference: CommitReference,
+
+        /// rewrite commits even if they are already pushed
+        #[arg(long)]
+        force: bool,
+    },
+    /// Explain and propose resolutions for the conflicts of an in-progress merge, rebase or cherry-pick
+    Conflicts,
+    /// Generate one combined message for the commits of a range being squashed
+    SquashMessage {
+        /// `<from>..<to>`, or `<from>` as a shorthand for `<from>..HEAD`
+        #[arg(group = "source", value_parser = clap::value_parser!(CommitReference))]
+        reference: Option<CommitReference>,
+
+        /// rewrite a squash message file in place, for use as GIT_EDITOR during `git rebase -i`;
+        /// other files open in your usual editor
+        #[arg(long, value_name = "FILE", group = "source")]
+        editor: Option<PathBuf>,
+    },
+    /// Ask a question about the repository history
+    Ask {
+        question: String,
+
+        /// only search commits touching these paths
+        #[arg(long = "path", value_name = "PATH")]
+        paths: Vec<String>,
+
+        /// only search commits more recent than this date
+        #[arg(long)]
+        since: Option<String>,
+
+        /// only search commits older than this date
+        #[arg(long)]
+        until: Option<String>,
+
+        /// only search commits by this author
+        #[arg(long)]
+        author: Option<String>,
+    },
+    /// Translate what you want to do into git commands, and run them after confirmation
+    #[command(name = "do")]
+    Do { intent: String },
+    /// Summarize your recent activity into a standup note
+    Standup {
+        /// only include commits more recent than this date
+        #[arg(long, default_value = "yesterday")]
+        since: String,
+
+        /// author to summarize, a pattern as for `git log --author`; `me` uses the
+        /// repository's user.email
+        #[arg(long, default_value = "me")]
+        author: String,
+
+        /// repositories to include, defaults to the current one
+        #[arg(long, num_args = 1.., value_name = "DIR")]
+        repos: Vec<PathBuf>,
+    },
+    /// Check a commit message against the configured convention
+    LintMessage {
+        /// message file, as passed to the commit-msg hook
+        #[arg(group = "target")]
+        file: Option<PathBuf>,
+
+        /// check the message of an existing commit
+        #[arg(long, value_name = "SHA", group = "target")]
+        commit: Option<String>,
+
+        /// also ask the model whether the message describes the diff
+        #[arg(long)]
+        check_diff: bool,
+
+        /// install as the commit-msg hook of the current repository
+        #[arg(long, group = "target")]
+        install_hook: bool,
+    },
+    /// Recommend the next semantic version from the commits since the latest tag
+    VersionBump {
+        /// create an annotated tag for the recommended version
+        #[arg(long)]
+        tag: bool,
+    },
+    /// Manage the response cache in ~/.gitai/cache
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+    /// List the models offered by the configured provider
+    Models,
+}
+
+#[derive(Subcommand, Clone, Copy)]
+pub enum CacheAction {
+    /// Remove every cached response
+    Clear,
+    /// Show the number and size of cached responses
+    Stats,
 }
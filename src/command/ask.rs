@@ -0,0 +1,177 @@
+use crate::llm::LLMComplete;
+
+use super::Command;
+use crate::git_entity::commit::{Commit, CommitError};
+use crate::git_entity::log::LogQuery;
+use crate::llm::{GenerationParams, LLMProvider, Message, Role};
+use crate::util::print_markdown;
+use indoc::{formatdoc, indoc};
+use spinoff::{spinners, Color, Spinner};
+
+use crate::error::GitAIError;
+use async_trait::async_trait;
+
+const MAX_SEARCHES: usize = 4;
+const COMMITS_PER_SEARCH: usize = 10;
+const MAX_DIFF_CHARS: usize = 2000;
+
+pub struct AskCommand {
+    pub question: String,
+    pub constraints: LogQuery,
+}
+
+impl AskCommand {
+    fn get_system_prompt() -> String {
+        String::from(indoc! {r#"
+            You are a helpful assistant that answers questions about the history of a Git repository.
+            You cannot see the repository directly, but you can search its history with `git log`.
+
+            To search, reply with only a fenced code block tagged `search` containing a JSON object
+            with any of these keys:
+            - "pickaxe": string, commits that add or remove occurrences of this exact string (git log -S)
+            - "regex": string, commits with added or removed lines matching this regex (git log -G)
+            - "grep": string, commits whose message matches this pattern (git log --grep)
+            - "author": string
+            - "since": string, e.g. "2024-01-01" or "6 months ago"
+            - "until": string
+            - "paths": array of path filters
+
+            You will receive the matching commits. You may search up to 4 times, refining the query.
+            When you have enough information, reply with the answer in markdown instead of a search.
+            Cite the commits your answer is based on by their short hash, and say so when the
+            history does not contain an answer.
+        "#})
+    }
+
+    fn parse_search(response: &str) -> Option<LogQuery> {
+        let start = response.find("```search")? + "```search".len();
+        let end = response[start..].find("```")? + start;
+
+        serde_json::from_str(response[start..end].trim()).ok()
+    }
+
+    fn format_commits(hashes: Vec<String>) -> Result<String, GitAIError> {
+        // Merges and empty commits have no diff to show, the others carry the answer
+        let mut commits = Vec::new();
+        for hash in hashes {
+            match Commit::new(hash) {
+                Ok(commit) => commits.push(commit),
+                Err(GitAIError::GitCommitError(CommitError::EmptyDiff(_))) => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        if commits.is_empty() {
+            return Ok("No commits found.".to_string());
+        }
+
+        Ok(commits
+            .iter()
+            .map(|commit| {
+                let diff = match commit.diff.char_indices().nth(MAX_DIFF_CHARS) {
+                    Some((index, _)) => format!("{}\n[diff truncated]", &commit.diff[..index]),
+                    None => commit.diff.clone(),
+                };
+
+                formatdoc! {"
+                    commit {hash} | {author} <{email}> | {date}
+                    {message}
+                    ```diff
+                    {diff}
+                    ```
+                    ",
+                    hash = &commit.full_hash[..7],
+                    author = commit.author_name,
+                    email = commit.author_email,
+                    date = commit.date,
+                    message = commit.message,
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+
+    fn get_user_prompt(&self) -> String {
+        let constraints = self.constraints.describe();
+        if constraints.is_empty() {
+            return format!("Question: {}", self.question);
+        }
+
+        formatdoc! {"
+            Question: {question}
+
+            Every search is restricted with: {constraints}
+            ",
+            question = self.question,
+        }
+    }
+
+    async fn complete(
+        llm: &LLMProvider,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<String, GitAIError> {
+        let mut spinner = Spinner::new(
+            spinners::Dots,
+            "Thinking...".to_string(),
+            Color::Green,
+        );
+
+        let response = llm.complete(messages, params).await;
+
+        spinner.stop();
+
+        Ok(response?)
+    }
+}
+
+#[async_trait]
+impl Command for AskCommand {
+    async fn execute(&self, llm: LLMProvider, params: GenerationParams) -> Result<(), GitAIError> {
+        let mut messages = vec![
+            Message {
+                role: Role::System,
+                content: Self::get_system_prompt(),
+            },
+            Message {
+                role: Role::User,
+                content: self.get_user_prompt(),
+            },
+        ];
+
+        for _ in 0..MAX_SEARCHES {
+            let response = Self::complete(&llm, &messages, &params).await?;
+
+            let Some(query) = Self::parse_search(&response) else {
+                print_markdown(response)?;
+                return Ok(());
+            };
+
+            messages.push(Message {
+                role: Role::Assistant,
+                content: response,
+            });
+
+            let query = query.merged_with(&self.constraints);
+            println!("Searching history: git log {}", query.describe());
+
+            let results = Self::format_commits(query.run(COMMITS_PER_SEARCH)?)?;
+            messages.push(Message {
+                role: Role::User,
+                content: format!("Search results:\n\n{}", results),
+            });
+        }
+
+        messages.push(Message {
+            role: Role::User,
+            content: "No searches left, answer with what you have found so far.".into(),
+        });
+        let response = Self::complete(&llm, &messages, &params).await?;
+
+        match Self::parse_search(&response) {
+            Some(_) => println!("No answer found in the repository history"),
+            None => print_markdown(response)?,
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,304 @@
+use crate::llm::LLMComplete;
+
+use super::generate::{commit_type_names, default_commit_types};
+use super::AIPrompt;
+use super::Command;
+use crate::git_entity::branch::Branch;
+use crate::git_entity::diff::Diff;
+use crate::git_entity::GitEntity;
+use crate::llm::{AIPromptError, GenerationParams, LLMError, LLMProvider, Message, Role};
+use dialoguer::{console::Term, theme::ColorfulTheme, Confirm, Select};
+use indoc::{formatdoc, indoc};
+use spinoff::{spinners, Color, Spinner};
+
+use crate::error::GitAIError;
+use async_trait::async_trait;
+
+pub const DEFAULT_BRANCH_PATTERN: &str = "<type>/<ticket>-<slug>";
+
+/// In characters, a rambling summary would make a name too long to type
+const MAX_SLUG_LENGTH: usize = 40;
+
+pub enum BranchSource {
+    Description(String),
+    Changes(GitEntity),
+}
+
+pub struct BranchCommand {
+    pub source: BranchSource,
+    pub ticket: Option<String>,
+    pub pattern: String,
+    pub count: usize,
+}
+
+impl BranchCommand {
+    pub fn get_ai_prompt(&self) -> Result<AIPrompt, AIPromptError> {
+        let system_prompt = String::from(indoc! {"
+            You are a git branch name generator that follows these rules:
+            1. Output one suggestion per line and nothing else
+            2. Each line must be in the format: <type>: <short summary>
+            3. The summary is 2 to 6 lowercase words, no punctuation
+        "});
+
+        let context = match &self.source {
+            BranchSource::Description(description) => formatdoc! {"
+                Work description:
+                {description}
+                "
+            },
+            BranchSource::Changes(GitEntity::Diff(
+                Diff::WorkingTree { diff, .. } | Diff::CommitsRange { diff, .. },
+            )) => formatdoc! {"
+                Code diff:
+                ```diff
+                {diff}
+                ```
+                "
+            },
+            BranchSource::Changes(GitEntity::Commit(_)) => {
+                return Err(AIPromptError(
+                    "`branch` is only supported for descriptions and diffs".into(),
+                ));
+            }
+        };
+
+        let user_prompt = formatdoc! {"
+            Suggest {count} distinct git branch names for the work described below.
+
+            Choose a type from the type-to-description JSON below that best describes the work:
+            {commit_types}
+
+            {context}
+            ",
+            count = self.count,
+            commit_types = default_commit_types(),
+        };
+
+        Ok(AIPrompt {
+            system_prompt,
+            user_prompt,
+        })
+    }
+
+    /// Lowercase words joined by dashes, cut after the last whole word that fits. Letters
+    /// such as `é` are kept, splitting on them would cut words in two
+    fn slugify(summary: &str) -> String {
+        let mut slug = String::new();
+        for word in summary
+            .to_lowercase()
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+        {
+            if !slug.is_empty() {
+                if slug.chars().count() + 1 + word.chars().count() > MAX_SLUG_LENGTH {
+                    break;
+                }
+                slug.push('-');
+            }
+            slug.push_str(word);
+        }
+        slug
+    }
+
+    fn render_name(&self, commit_type: &str, slug: &str) -> String {
+        let ticket = self.ticket.as_deref().unwrap_or("").trim();
+
+        let mut name = self.pattern.clone();
+        if ticket.is_empty() {
+            // Drop the placeholder together with the separator that joins it to the slug
+            for separator in ["-", "_", "/"] {
+                name = name.replace(&format!("<ticket>{separator}"), "");
+                name = name.replace(&format!("{separator}<ticket>"), "");
+            }
+        }
+
+        name.replace("<type>", commit_type)
+            .replace("<ticket>", ticket)
+            .replace("<slug>", slug)
+    }
+
+    fn parse_suggestions(&self, response: &str) -> Vec<String> {
+        let commit_types = commit_type_names();
+        let mut names: Vec<String> = Vec::new();
+
+        for line in response.lines() {
+            let line = line
+                .trim()
+                .trim_start_matches(|c: char| c.is_ascii_digit() || "-*.) `".contains(c))
+                .trim_end_matches('`');
+
+            let Some((commit_type, summary)) = line.split_once(':') else {
+                continue;
+            };
+
+            let commit_type = commit_type.trim().to_lowercase();
+            let commit_type = commit_type
+                .split_once('(')
+                .map_or(commit_type.as_str(), |(t, _)| t);
+            if !commit_types.iter().any(|t| t == commit_type) {
+                continue;
+            }
+
+            let slug = Self::slugify(summary);
+            if slug.is_empty() {
+                continue;
+            }
+
+            let name = self.render_name(commit_type, &slug);
+            if Branch::is_valid_name(&name).is_ok() && !names.contains(&name) {
+                names.push(name);
+            }
+        }
+
+        names.truncate(self.count);
+        names
+    }
+}
+
+#[async_trait]
+impl Command for BranchCommand {
+    async fn execute(&self, llm: LLMProvider, params: GenerationParams) -> Result<(), GitAIError> {
+        let mut spinner = Spinner::new(
+            spinners::Dots,
+            "Generating branch names...".to_string(),
+            Color::Green,
+        );
+
+        let ai_prompt = self.get_ai_prompt().map_err(LLMError::from)?;
+
+        let system_message = Message {
+            role: Role::System,
+            content: ai_prompt.system_prompt,
+        };
+
+        let user_message = Message {
+            role: Role::User,
+            content: ai_prompt.user_prompt,
+        };
+
+        let messages = vec![system_message, user_message];
+
+        let response = llm.complete(&messages, &params).await;
+
+        spinner.stop();
+
+        let names = match response {
+            Ok(response) => self.parse_suggestions(&response),
+            Err(LLMError::DryRun) => return Err(LLMError::DryRun.into()),
+            Err(e) => {
+                println!("Error: {}", GitAIError::from(e));
+                return Ok(());
+            }
+        };
+
+        if names.is_empty() {
+            println!("No valid branch names were suggested");
+            return Ok(());
+        }
+
+        let theme = ColorfulTheme::default();
+        let selection = Select::with_theme(&theme)
+            .with_prompt("Select a branch name")
+            .items(&names)
+            .default(0)
+            .interact_on_opt(&Term::stderr())?;
+
+        let Some(selection) = selection else {
+            return Ok(());
+        };
+
+        let branch = Branch::new(names[selection].clone())?;
+
+        let create = Confirm::with_theme(&theme)
+            .with_prompt(format!("Create and switch to '{}'?", branch.name))
+            .default(false)
+            .interact_on(&Term::stderr())?;
+
+        if create {
+            branch.create_and_switch()?;
+            println!("Switched to a new branch '{}'", branch.name);
+        } else {
+            println!("{}", branch.name);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(ticket: Option<&str>, pattern: &str) -> BranchCommand {
+        BranchCommand {
+            source: BranchSource::Description("add retry".to_string()),
+            ticket: ticket.map(String::from),
+            pattern: pattern.to_string(),
+            count: 5,
+        }
+    }
+
+    #[test]
+    fn parses_numbered_and_bulleted_suggestions() {
+        let response = "1. feat: add upload retry\n2) fix(client): Handle timeouts!\n\
+            - `refactor: split the client`\n* docs: retry settings\nSome prose: not a type\n";
+
+        assert_eq!(
+            command(None, DEFAULT_BRANCH_PATTERN).parse_suggestions(response),
+            [
+                "feat/add-upload-retry",
+                "fix/handle-timeouts",
+                "refactor/split-the-client",
+                "docs/retry-settings",
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_the_first_suggestions_without_duplicates() {
+        let response = "feat: add retry\nfeat: Add retry\nfix: a\nfix: b\nfix: c";
+
+        assert_eq!(
+            command(None, DEFAULT_BRANCH_PATTERN)
+                .parse_suggestions(response)
+                .len(),
+            4
+        );
+        assert_eq!(
+            BranchCommand {
+                count: 2,
+                ..command(None, DEFAULT_BRANCH_PATTERN)
+            }
+            .parse_suggestions(response),
+            ["feat/add-retry", "fix/a"]
+        );
+    }
+
+    #[test]
+    fn cuts_long_slugs_after_a_whole_word() {
+        let slug = BranchCommand::slugify(
+            "retry every upload of the client when the server answers with a gateway timeout",
+        );
+
+        assert_eq!(slug, "retry-every-upload-of-the-client-when");
+        assert!(slug.len() <= MAX_SLUG_LENGTH);
+        assert_eq!(BranchCommand::slugify(&"a".repeat(50)), "a".repeat(50));
+    }
+
+    #[test]
+    fn keeps_non_ascii_words_whole() {
+        assert_eq!(BranchCommand::slugify("Fix the Café menu: résumé"), "fix-the-café-menu-résumé");
+        assert_eq!(BranchCommand::slugify("🚀 --- !"), "");
+    }
+
+    #[test]
+    fn renders_the_ticket_into_the_pattern() {
+        let with_ticket = command(Some("PROJ-42"), DEFAULT_BRANCH_PATTERN);
+        let without_ticket = command(None, DEFAULT_BRANCH_PATTERN);
+        let ticket_last = command(Some(" "), "<slug>_<ticket>");
+
+        assert_eq!(with_ticket.render_name("feat", "retry"), "feat/PROJ-42-retry");
+        assert_eq!(without_ticket.render_name("feat", "retry"), "feat/retry");
+        assert_eq!(ticket_last.render_name("fix", "retry"), "retry");
+    }
+}
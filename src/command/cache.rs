@@ -0,0 +1,49 @@
+use super::Command;
+use crate::cli::CacheAction;
+use crate::llm::cache::ResponseCache;
+use crate::llm::{GenerationParams, LLMProvider};
+
+use crate::error::GitAIError;
+use async_trait::async_trait;
+
+pub struct CacheCommand {
+    pub action: CacheAction,
+    pub cache: ResponseCache,
+}
+
+fn format_size(bytes: u64) -> String {
+    if bytes < 1024 * 1024 {
+        format!("{:.1} KB", bytes as f64 / 1024.0)
+    } else {
+        format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+    }
+}
+
+#[async_trait]
+impl Command for CacheCommand {
+    async fn execute(
+        &self,
+        _llm: LLMProvider,
+        _params: GenerationParams,
+    ) -> Result<(), GitAIError> {
+        match self.action {
+            CacheAction::Clear => {
+                let removed = self.cache.clear()?;
+                println!("Removed {} cached responses", removed);
+            }
+            CacheAction::Stats => {
+                let stats = self.cache.stats()?;
+                println!("Cache directory: {}", self.cache.dir().display());
+                println!("Entries: {} ({} expired)", stats.entries, stats.expired);
+                println!(
+                    "Size: {} of {}",
+                    format_size(stats.bytes),
+                    format_size(self.cache.max_bytes())
+                );
+                println!("Entries expire after {} days", self.cache.ttl().as_secs() / 86400);
+            }
+        }
+
+        Ok(())
+    }
+}
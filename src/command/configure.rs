@@ -1,35 +1,172 @@
 use crate::cli::LLMProviderType;
+use crate::config::GitAIConfig;
 use crate::error::GitAIError;
-use crate::llm::LLMProvider;
+use crate::llm::{get_llm, same_model, GenerationParams, LLMComplete, LLMError, LLMProvider};
 use async_trait::async_trait;
 use dialoguer::{console::Term, theme::ColorfulTheme, Input, Select};
 use std::env;
 use std::fs;
+use std::io;
 
 use super::Command;
 
-pub struct ConfigureCommand {}
+/// Settings asked by `configure`, dropped when the chosen provider does not use them
+const CONFIGURED_KEYS: [&str; 5] = [
+    "PROVIDER",
+    "MODEL",
+    "BASE_URL",
+    "AZURE_ENDPOINT",
+    "AZURE_DEPLOYMENT",
+];
+
+/// Writes `values` over the settings of an existing .env file, in place, keeping every other
+/// line such as comments, headers, fallbacks and the keys of other providers
+fn merge_env(existing: &str, values: &[(&str, String)]) -> String {
+    let mut written = Vec::new();
+    let mut content = String::new();
+
+    for line in existing.lines() {
+        let key = line.split_once('=').map(|(key, _)| key);
+        match key.and_then(|key| values.iter().find(|(name, _)| *name == key)) {
+            Some((name, value)) => {
+                // A repeated setting is written once, where it first was
+                if !written.contains(name) {
+                    content.push_str(&format!("{}={}\n", name, value));
+                    written.push(*name);
+                }
+            }
+            None if key.is_some_and(|key| CONFIGURED_KEYS.contains(&key)) => {}
+            None => {
+                content.push_str(line);
+                content.push('\n');
+            }
+        }
+    }
+
+    for (name, value) in values {
+        if !written.contains(name) {
+            content.push_str(&format!("{}={}\n", name, value));
+        }
+    }
+    content
+}
+
+pub struct ConfigureCommand {
+    pub config: GitAIConfig,
+}
 
 impl ConfigureCommand {
-    fn get_default_model_for_provider(provider: &LLMProviderType) -> &'static str {
+    /// Local providers run without an API key
+    fn get_environment_variable_name_for_provider(provider: &LLMProviderType) -> Option<&'static str> {
         match provider {
-            LLMProviderType::Openai => "gpt-4o-mini",
-            LLMProviderType::Phind => "Phind-70B",
-            LLMProviderType::Anthropic => "claude-3-opus-20240229",
-            LLMProviderType::Grok => "mixtral-8x7b-32768",
+            LLMProviderType::Openai => Some("OPENAI_API_KEY"),
+            LLMProviderType::Phind => Some("PHIND_API_KEY"),
+            LLMProviderType::Anthropic => Some("ANTHROPIC_API_KEY"),
+            LLMProviderType::Grok => Some("GROQ_API_KEY"),
+            LLMProviderType::Ollama | LLMProviderType::Replay | LLMProviderType::Exec => None,
+            LLMProviderType::OpenaiCompatible => Some("OPENAI_COMPATIBLE_API_KEY"),
+            LLMProviderType::Gemini => Some("GEMINI_API_KEY"),
+            LLMProviderType::Azure => Some("AZURE_OPENAI_API_KEY"),
         }
     }
 
-    fn get_environment_variable_name_for_provider(provider: &LLMProviderType) -> &'static str {
-        match provider {
-            LLMProviderType::Openai => "OPENAI_API_KEY",
-            LLMProviderType::Phind => "PHIND_API_KEY",
-            LLMProviderType::Anthropic => "ANTHROPIC_API_KEY",
-            LLMProviderType::Grok => "GROQ_API_KEY",
+    /// The models of the selected provider, asked with the settings entered so far and the
+    /// current proxy and certificates
+    async fn list_models(
+        &self,
+        provider: LLMProviderType,
+        settings: &[(&str, String)],
+        api_key: Option<&str>,
+    ) -> Result<Vec<String>, LLMError> {
+        let setting = |key: &str| {
+            settings
+                .iter()
+                .find(|(name, _)| *name == key)
+                .map(|(_, value)| value.clone())
+        };
+        let same_provider = provider == self.config.provider;
+
+        let config = GitAIConfig {
+            provider,
+            model: None,
+            api_key: api_key.map(String::from),
+            fallback: Vec::new(),
+            base_url: setting("BASE_URL"),
+            auth_scheme: self.config.auth_scheme.clone().filter(|_| same_provider),
+            azure_endpoint: setting("AZURE_ENDPOINT"),
+            // Only there for the provider to be built, Azure does not list its deployments
+            azure_deployment: Some(String::new()),
+            replay_file: None,
+            record_file: None,
+            dry_run: false,
+            ..self.config.clone()
+        };
+
+        let llm = get_llm(&config, false).map_err(LLMError::from)?;
+        let mut models = llm
+            .list_models()
+            .await?
+            .into_iter()
+            .map(|model| model.id)
+            .collect::<Vec<_>>();
+        models.sort();
+        Ok(models)
+    }
+
+    /// A choice among the provider's models, or a name typed in when they cannot be listed
+    async fn select_model(
+        &self,
+        theme: &ColorfulTheme,
+        provider: LLMProviderType,
+        settings: &[(&str, String)],
+        api_key: Option<&str>,
+    ) -> Result<String, GitAIError> {
+        let default_model = provider.default_model();
+
+        let models = match self.list_models(provider, settings, api_key).await {
+            Ok(models) => models,
+            Err(LLMError::Unsupported(_)) => Vec::new(),
+            Err(e) => {
+                eprintln!("Could not list the models: {}", e);
+                Vec::new()
+            }
+        };
+
+        if !models.is_empty() {
+            let mut items = models.clone();
+            items.push("Other...".to_string());
+
+            let selection = Select::with_theme(theme)
+                .with_prompt("Model")
+                .items(&items)
+                .default(
+                    models
+                        .iter()
+                        .position(|model| same_model(model, default_model))
+                        .unwrap_or(0),
+                )
+                .max_length(15)
+                .interact_on(&Term::stderr())?;
+
+            if selection < models.len() {
+                return Ok(models[selection].clone());
+            }
         }
+
+        // Input model name with default value based on provider
+        Ok(Input::with_theme(theme)
+            .with_prompt("Model name")
+            .default(default_model.to_string())
+            .interact_on(&Term::stderr())?)
     }
 
-    fn save_config(&self, provider: LLMProviderType, model: &str, api_key: &str) -> Result<(), GitAIError> {
+    fn save_config(
+        &self,
+        provider: LLMProviderType,
+        model: &str,
+        settings: &[(&str, String)],
+        api_key: Option<&str>,
+    ) -> Result<(), GitAIError> {
         // Create ~/.gitai directory if it doesn't exist
         let home_dir = dirs::home_dir().ok_or_else(|| {
             GitAIError::ConfigError("Could not determine home directory".to_string())
@@ -45,21 +182,39 @@ impl ConfigureCommand {
             LLMProviderType::Phind => "phind",
             LLMProviderType::Anthropic => "anthropic",
             LLMProviderType::Grok => "grok",
+            LLMProviderType::Ollama => "ollama",
+            LLMProviderType::OpenaiCompatible => "openai-compatible",
+            LLMProviderType::Gemini => "gemini",
+            LLMProviderType::Azure => "azure",
+            LLMProviderType::Replay => "replay",
+            LLMProviderType::Exec => "exec",
         };
 
         // Create or update .env file to persist configuration
         let env_file = config_dir.join(".env");
-        
+
+        let mut values = vec![("PROVIDER", provider_str.to_string()), ("MODEL", model.to_string())];
+        values.extend(settings.iter().cloned());
+
         // Get the API key environment variable name for the provider
-        let api_key_env_var = Self::get_environment_variable_name_for_provider(&provider);
-        
-        // Create the content for the .env file
-        let env_content = format!(
-            "PROVIDER={}\nMODEL={}\n{}={}\n",
-            provider_str, model, api_key_env_var, api_key
-        );
+        if let (Some(api_key_env_var), Some(api_key)) =
+            (Self::get_environment_variable_name_for_provider(&provider), api_key)
+        {
+            values.push((api_key_env_var, api_key.to_string()));
+        }
+
+        let existing = match fs::read_to_string(&env_file) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => {
+                return Err(GitAIError::ConfigError(format!(
+                    "Could not read config file: {}",
+                    e
+                )))
+            }
+        };
 
-        fs::write(&env_file, env_content).map_err(|e| {
+        fs::write(&env_file, merge_env(&existing, &values)).map_err(|e| {
             GitAIError::ConfigError(format!("Could not write config file: {}", e))
         })?;
 
@@ -74,11 +229,15 @@ impl ConfigureCommand {
 
 #[async_trait]
 impl Command for ConfigureCommand {
-    async fn execute(&self, _llm: LLMProvider) -> Result<(), GitAIError> {
+    async fn execute(
+        &self,
+        _llm: LLMProvider,
+        _params: GenerationParams,
+    ) -> Result<(), GitAIError> {
         let theme = ColorfulTheme::default();
         
         // Select provider
-        let items = vec!["OpenAI", "Phind", "Anthropic", "Groq"];
+        let items = vec!["OpenAI", "Phind", "Anthropic", "Groq", "Ollama (local)", "OpenAI compatible", "Gemini", "Azure OpenAI"];
         let selection = Select::with_theme(&theme)
             .with_prompt("Select your LLM provider")
             .items(&items)
@@ -90,30 +249,99 @@ impl Command for ConfigureCommand {
             1 => LLMProviderType::Phind,
             2 => LLMProviderType::Anthropic,
             3 => LLMProviderType::Grok,
+            4 => LLMProviderType::Ollama,
+            5 => LLMProviderType::OpenaiCompatible,
+            6 => LLMProviderType::Gemini,
+            7 => LLMProviderType::Azure,
             _ => unreachable!(),
         };
 
-        // Default model based on selected provider
-        let default_model = Self::get_default_model_for_provider(&provider);
-        
-        // Input model name with default value based on provider
-        let model: String = Input::with_theme(&theme)
-            .with_prompt("Model name")
-            .default(default_model.to_string())
-            .interact_on(&Term::stderr())?;
+        // vLLM, LM Studio, LiteLLM, OpenRouter... and Azure resources have no default URL
+        let url_setting = match provider {
+            LLMProviderType::OpenaiCompatible => {
+                Some(("BASE_URL", "Base URL (e.g. http://localhost:8000/v1)"))
+            }
+            LLMProviderType::Azure => Some((
+                "AZURE_ENDPOINT",
+                "Resource endpoint (e.g. https://my-resource.openai.azure.com)",
+            )),
+            _ => None,
+        };
+
+        let mut settings = Vec::new();
+        if let Some((key, prompt)) = url_setting {
+            let url: String = Input::with_theme(&theme)
+                .with_prompt(prompt)
+                .interact_on(&Term::stderr())?;
+            settings.push((key, url));
+        }
 
         // Input API key
-        let api_key_var = Self::get_environment_variable_name_for_provider(&provider);
-        let default_api_key = env::var(api_key_var).unwrap_or_default();
-        
-        let api_key: String = Input::with_theme(&theme)
-            .with_prompt("API Key")
-            .default(default_api_key)
-            .interact_on(&Term::stderr())?;
+        let api_key = match Self::get_environment_variable_name_for_provider(&provider) {
+            Some(api_key_var) => {
+                let default_api_key = env::var(api_key_var).unwrap_or_default();
+
+                let api_key: String = Input::with_theme(&theme)
+                    .with_prompt("API Key")
+                    .default(default_api_key)
+                    .interact_on(&Term::stderr())?;
+                Some(api_key)
+            }
+            None => None,
+        };
+
+        // The key and URL are needed to list the models
+        let model = self
+            .select_model(&theme, provider, &settings, api_key.as_deref())
+            .await?;
+
+        if provider == LLMProviderType::Azure {
+            let deployment: String = Input::with_theme(&theme)
+                .with_prompt("Deployment name")
+                .default(model.clone())
+                .interact_on(&Term::stderr())?;
+            settings.push(("AZURE_DEPLOYMENT", deployment));
+        }
 
         // Save config
-        self.save_config(provider, &model, &api_key)?;
+        self.save_config(provider, &model, &settings, api_key.as_deref())?;
 
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_other_settings_of_the_config_file() {
+        let existing = "# Work setup\nPROVIDER=openai-compatible\nBASE_URL=http://localhost:8000/v1\n\
+            HEADER=X-Team: core\nMODEL=llama3\nOPENAI_API_KEY=sk-old\nFALLBACK=ollama\n";
+        let values = [
+            ("PROVIDER", "openai".to_string()),
+            ("MODEL", "gpt-4o-mini".to_string()),
+            ("OPENAI_API_KEY", "sk-new".to_string()),
+        ];
+
+        assert_eq!(
+            merge_env(existing, &values),
+            "# Work setup\nPROVIDER=openai\nHEADER=X-Team: core\nMODEL=gpt-4o-mini\n\
+            OPENAI_API_KEY=sk-new\nFALLBACK=ollama\n"
+        );
+    }
+
+    #[test]
+    fn adds_the_settings_missing_from_the_config_file() {
+        let values = [
+            ("PROVIDER", "azure".to_string()),
+            ("MODEL", "gpt-4o".to_string()),
+            ("AZURE_ENDPOINT", "https://team.openai.azure.com".to_string()),
+        ];
+
+        assert_eq!(
+            merge_env("PROVIDER=ollama\nPROVIDER=ollama\nTEMPERATURE=0.3\n", &values),
+            "PROVIDER=azure\nTEMPERATURE=0.3\nMODEL=gpt-4o\nAZURE_ENDPOINT=https://team.openai.azure.com\n"
+        );
+    }
+}
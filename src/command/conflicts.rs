@@ -0,0 +1,277 @@
+use crate::llm::LLMComplete;
+
+use super::AIPrompt;
+use super::Command;
+use crate::git_entity::commit::Commit;
+use crate::git_entity::conflict::{ConflictHunk, ConflictedFile, Operation};
+use crate::llm::{GenerationParams, LLMError, LLMProvider, Message, Role};
+use crate::util::print_markdown;
+use dialoguer::{console::Term, theme::ColorfulTheme, Confirm};
+use indoc::{formatdoc, indoc};
+use spinoff::{spinners, Color, Spinner};
+
+use crate::error::GitAIError;
+use async_trait::async_trait;
+
+const COMMITS_PER_SIDE: usize = 5;
+
+pub struct ConflictsCommand {}
+
+struct Sides {
+    ours_label: &'static str,
+    theirs_label: &'static str,
+}
+
+impl ConflictsCommand {
+    fn get_sides(operation: Operation) -> Sides {
+        match operation {
+            Operation::Merge => Sides {
+                ours_label: "current branch (HEAD)",
+                theirs_label: "branch being merged (MERGE_HEAD)",
+            },
+            Operation::Rebase => Sides {
+                ours_label: "upstream being rebased onto (HEAD)",
+                theirs_label: "commit being replayed (REBASE_HEAD)",
+            },
+            Operation::CherryPick => Sides {
+                ours_label: "current branch (HEAD)",
+                theirs_label: "commit being cherry-picked (CHERRY_PICK_HEAD)",
+            },
+        }
+    }
+
+    fn format_commits(range: &str, path: &str) -> Result<String, GitAIError> {
+        let commits = Commit::list_touching(range, path, COMMITS_PER_SIDE)?
+            .into_iter()
+            .map(Commit::new)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if commits.is_empty() {
+            return Ok("(no commits found)".to_string());
+        }
+
+        Ok(commits
+            .iter()
+            .map(|commit| {
+                format!(
+                    "- {} ({}): {}",
+                    &commit.full_hash[..7],
+                    commit.author_name,
+                    commit.message.replace('\n', "\n  ")
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+
+    fn get_ai_prompt(
+        operation: Operation,
+        file: &ConflictedFile,
+        hunk: &ConflictHunk,
+        ours_commits: &str,
+        theirs_commits: &str,
+    ) -> AIPrompt {
+        let system_prompt = String::from(indoc! {"
+            You are a helpful assistant that explains and resolves Git merge conflicts.
+            Explain briefly what each side intended to change, based on its code and commits.
+            Then propose a resolution that preserves the intent of both sides where possible.
+            Use markdown, with exactly these three headings: Ours, Theirs, Resolution.
+            Under Resolution, give a one sentence rationale followed by a single fenced code block
+            containing only the exact text that replaces the whole conflict, without conflict markers.
+        "});
+
+        let sides = Self::get_sides(operation);
+
+        let user_prompt = formatdoc! {"
+            Context - Conflict in `{path}` during a {operation}:
+
+            Ours is the {ours_label}. Commits on this side that touched the file:
+            {ours_commits}
+
+            Theirs is the {theirs_label}. Commits on this side that touched the file:
+            {theirs_commits}
+
+            Base (common ancestor):
+            ```
+            {base}```
+
+            Ours:
+            ```
+            {ours}```
+
+            Theirs:
+            ```
+            {theirs}```
+            ",
+            path = file.path,
+            operation = operation.name(),
+            ours_label = sides.ours_label,
+            theirs_label = sides.theirs_label,
+            base = hunk.base.as_deref().unwrap_or("(not available)\n"),
+            ours = hunk.ours,
+            theirs = hunk.theirs,
+        };
+
+        AIPrompt {
+            system_prompt,
+            user_prompt,
+        }
+    }
+
+    /// Extracts the content of the first fenced code block of the response, the one under
+    /// Resolution; a block that still has conflict markers is no resolution
+    fn extract_resolution(response: &str) -> Option<String> {
+        let mut current: Option<String> = None;
+
+        for line in response.split_inclusive('\n') {
+            if line.trim_start().starts_with("```") {
+                match current.take() {
+                    Some(block) => {
+                        let has_markers = block
+                            .lines()
+                            .any(|line| line.starts_with("<<<<<<<") || line.starts_with(">>>>>>>"));
+                        return (!has_markers).then_some(block);
+                    }
+                    None => current = Some(String::new()),
+                }
+            } else if let Some(block) = current.as_mut() {
+                block.push_str(line);
+            }
+        }
+
+        None
+    }
+}
+
+#[async_trait]
+impl Command for ConflictsCommand {
+    async fn execute(&self, llm: LLMProvider, params: GenerationParams) -> Result<(), GitAIError> {
+        let operation = Operation::detect()?;
+        let (files, skipped) = ConflictedFile::list()?;
+        let theme = ColorfulTheme::default();
+
+        print_markdown(formatdoc! {"
+            # Conflicts: {operation} in progress
+            {files}
+            ",
+            operation = operation.name(),
+            files = files
+                .iter()
+                .map(|file| format!("* `{}` ({} conflicts)", file.path, file.hunks.len()))
+                .chain(skipped.iter().map(|path| format!("* `{}` (not text, skipped)", path)))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        })?;
+
+        let incoming = operation.incoming_ref();
+        for file in &files {
+            let ours_commits = Self::format_commits(&format!("{}..HEAD", incoming), &file.path)?;
+            let theirs_commits = Self::format_commits(&format!("HEAD..{}", incoming), &file.path)?;
+
+            let mut resolutions = vec![None; file.hunks.len()];
+            for (index, hunk) in file.hunks.iter().enumerate() {
+                print_markdown(format!(
+                    "## `{}` conflict {}/{}",
+                    file.path,
+                    index + 1,
+                    file.hunks.len()
+                ))?;
+
+                let mut spinner = Spinner::new(
+                    spinners::Dots,
+                    "Analyzing conflict...".to_string(),
+                    Color::Green,
+                );
+
+                let ai_prompt =
+                    Self::get_ai_prompt(operation, file, hunk, &ours_commits, &theirs_commits);
+
+                let system_message = Message {
+                    role: Role::System,
+                    content: ai_prompt.system_prompt,
+                };
+
+                let user_message = Message {
+                    role: Role::User,
+                    content: ai_prompt.user_prompt,
+                };
+
+                let messages = vec![system_message, user_message];
+
+                let response = llm.complete(&messages, &params).await;
+
+                spinner.stop();
+
+                let response = match response {
+                    Ok(response) => response,
+                    Err(LLMError::DryRun) => return Err(LLMError::DryRun.into()),
+                    Err(e) => {
+                        println!("Error: {}", GitAIError::from(e));
+                        continue;
+                    }
+                };
+
+                print_markdown(response.clone())?;
+
+                let Some(resolution) = Self::extract_resolution(&response) else {
+                    println!("No resolution proposed, leaving this conflict as is");
+                    continue;
+                };
+
+                let apply = Confirm::with_theme(&theme)
+                    .with_prompt("Apply the proposed resolution?")
+                    .default(false)
+                    .interact_on(&Term::stderr())?;
+
+                if apply {
+                    resolutions[index] = Some(resolution);
+                }
+            }
+
+            if resolutions.iter().all(Option::is_none) {
+                continue;
+            }
+
+            let has_markers = file.apply_resolutions(&resolutions)?;
+            if has_markers {
+                println!("Some conflicts in '{}' are still unresolved", file.path);
+                continue;
+            }
+
+            let stage = Confirm::with_theme(&theme)
+                .with_prompt(format!("All conflicts in '{}' resolved. Stage it?", file.path))
+                .default(false)
+                .interact_on(&Term::stderr())?;
+
+            if stage {
+                file.stage()?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn takes_the_first_code_block_as_the_resolution() {
+        let response = "## Resolution\nKeep both.\n```rust\nlet a = 1;\nlet b = 2;\n```\n\n\
+            The original was:\n```\nlet a = 1;\n```\n";
+
+        assert_eq!(
+            ConflictsCommand::extract_resolution(response).as_deref(),
+            Some("let a = 1;\nlet b = 2;\n")
+        );
+    }
+
+    #[test]
+    fn rejects_a_resolution_with_conflict_markers() {
+        let response = "```\n<<<<<<< HEAD\nlet a = 1;\n=======\nlet a = 2;\n>>>>>>> main\n```\n";
+
+        assert_eq!(ConflictsCommand::extract_resolution(response), None);
+        assert_eq!(ConflictsCommand::extract_resolution("No code here"), None);
+    }
+}
@@ -4,8 +4,9 @@ use super::AIPrompt;
 use super::Command;
 use crate::git_entity::diff::Diff;
 use crate::git_entity::GitEntity;
-use crate::llm::{AIPromptError, LLMProvider, Message, Role};
-use crate::util::print_markdown;
+use crate::llm::{AIPromptError, GenerationParams, LLMError, LLMProvider, Message, Role};
+use crate::util::{print_markdown, MarkdownStream};
+use futures_util::StreamExt;
 use indoc::{formatdoc, indoc};
 use spinoff::{spinners, Color, Spinner};
 
@@ -88,7 +89,7 @@ impl ExplainCommand {
 
 #[async_trait]
 impl Command for ExplainCommand {
-    async fn execute(&self, llm: LLMProvider) -> Result<(), GitAIError> {
+    async fn execute(&self, llm: LLMProvider, params: GenerationParams) -> Result<(), GitAIError> {
         print_markdown(self.git_entity.format_static_details())?;
 
         let mut spinner = Spinner::new(
@@ -111,19 +112,40 @@ impl Command for ExplainCommand {
 
         let messages = vec![system_message, user_message];
 
-        let response = llm.complete(&messages).await;
+        let mut stream = match llm.complete_stream(&messages, &params).await {
+            Ok(stream) => stream,
+            Err(LLMError::DryRun) => return Err(LLMError::DryRun.into()),
+            Err(e) => {
+                spinner.stop();
+                println!("Error: {}", GitAIError::from(e));
+                return Ok(());
+            }
+        };
 
-        spinner.stop();
+        // Keep the spinner until the model starts answering
+        let mut spinner = Some(spinner);
+        let mut markdown = MarkdownStream::default();
 
-        match response {
-            Ok(response) => {
-                print_markdown(response)?;
+        while let Some(delta) = stream.next().await {
+            if let Some(mut spinner) = spinner.take() {
+                spinner.stop();
             }
-            Err(e) => {
-                println!("Error: {}", GitAIError::from(e));
+
+            match delta {
+                Ok(delta) => markdown.push(&delta),
+                Err(e) => {
+                    markdown.finish();
+                    println!("Error: {}", GitAIError::from(e));
+                    return Ok(());
+                }
             }
         }
 
+        if let Some(mut spinner) = spinner {
+            spinner.stop();
+        }
+        markdown.finish();
+
         Ok(())
     }
 }
@@ -4,13 +4,18 @@ use super::AIPrompt;
 use super::Command;
 use crate::git_entity::diff::Diff;
 use crate::git_entity::GitEntity;
-use crate::llm::{AIPromptError, LLMProvider, Message, Role};
+use crate::llm::{
+    AIPromptError, GenerationParams, LLMError, LLMProvider, Message, ResponseSchema, Role,
+};
+use crate::util::extract_json_where;
 use indoc::{formatdoc, indoc};
+use serde::Deserialize;
+use serde_json::json;
 
 use crate::error::GitAIError;
 use async_trait::async_trait;
 
-fn default_commit_types() -> String {
+pub(super) fn default_commit_types() -> String {
     indoc! {r#"
     {
         "docs": "Documentation only changes",
@@ -29,6 +34,155 @@ fn default_commit_types() -> String {
     .to_string()
 }
 
+pub(super) fn commit_type_names() -> Vec<String> {
+    serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(&default_commit_types())
+        .map(|types| types.keys().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// The object the model answers with, assembled into the message locally.
+///
+/// Every field has a default so that a model without structured output can omit some.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct CommitMessage {
+    #[serde(rename = "type")]
+    commit_type: String,
+    scope: Option<String>,
+    subject: String,
+    body: Option<String>,
+    breaking: bool,
+    footers: Vec<String>,
+}
+
+impl CommitMessage {
+    /// A header can only be assembled with a type and a subject
+    fn is_complete(&self) -> bool {
+        !self.commit_type.trim().is_empty() && !self.subject.trim().is_empty()
+    }
+
+    /// `<type>(<scope>)!: <subject>`, then the body and the footers as their own paragraphs
+    fn assemble(&self) -> String {
+        let mut header = self.commit_type.trim().to_string();
+        if let Some(scope) = self.scope.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+            header.push_str(&format!("({})", scope));
+        }
+        if self.breaking {
+            header.push('!');
+        }
+        header.push_str(&format!(": {}", self.subject.trim()));
+
+        let mut paragraphs = vec![header];
+        if let Some(body) = self.body.as_deref().map(str::trim).filter(|b| !b.is_empty()) {
+            paragraphs.push(body.to_string());
+        }
+        let footers = self
+            .footers
+            .iter()
+            .map(|footer| footer.trim())
+            .filter(|footer| !footer.is_empty())
+            .collect::<Vec<_>>();
+        if !footers.is_empty() {
+            paragraphs.push(footers.join("\n"));
+        }
+
+        paragraphs.join("\n\n")
+    }
+}
+
+/// Strict schemas need every property required, so optional parts are empty strings and lists
+fn commit_message_schema() -> ResponseSchema {
+    ResponseSchema {
+        name: "commit_message".to_string(),
+        schema: json!({
+            "type": "object",
+            "properties": {
+                "type": { "type": "string", "enum": commit_type_names() },
+                "scope": { "type": "string" },
+                "subject": { "type": "string" },
+                "body": { "type": "string" },
+                "breaking": { "type": "boolean" },
+                "footers": { "type": "array", "items": { "type": "string" } },
+            },
+            "required": ["type", "scope", "subject", "body", "breaking", "footers"],
+            "additionalProperties": false,
+        }),
+    }
+}
+
+fn commit_message_prompt(diff: &str) -> AIPrompt {
+    let system_prompt = String::from(indoc! {"
+        You are a commit message generator that follows these rules:
+        1. Write in present tense
+        2. Be concise and direct
+        3. Answer only with a JSON object describing the commit message, without any explanations
+    "});
+
+    let user_prompt = formatdoc! {r#"
+        Describe a git commit message written in present tense for the following code diff as a JSON object with these fields:
+        - "type": the type from the type-to-description JSON below that best describes the git diff
+        - "scope": the part of the code the change is about, or an empty string
+        - "subject": what the change does, the header `<type>(<scope>): <subject>` must be at most 72 characters
+        - "body": why the change is made, only when the subject is not enough, otherwise an empty string
+        - "breaking": whether the change breaks backward compatibility
+        - "footers": trailers such as "Refs: #123" or "BREAKING CHANGE: <description>", usually none
+
+        Commit types:
+        {commit_types}
+        Focus on being accurate and concise. Exclude anything unnecessary such as translation.
+
+        Code diff:
+        ```diff
+        {diff}
+        ```
+        "#,
+        commit_types = default_commit_types(),
+    };
+
+    AIPrompt {
+        system_prompt,
+        user_prompt,
+    }
+}
+
+/// Asks for the commit message of `diff` as a JSON object, natively structured where the
+/// provider supports it, and assembles it
+pub(super) async fn generate_commit_message(
+    llm: &LLMProvider,
+    params: &GenerationParams,
+    diff: &str,
+) -> Result<String, LLMError> {
+    let ai_prompt = commit_message_prompt(diff);
+
+    let system_message = Message {
+        role: Role::System,
+        content: ai_prompt.system_prompt,
+    };
+
+    let user_message = Message {
+        role: Role::User,
+        content: ai_prompt.user_prompt,
+    };
+
+    let messages = vec![system_message, user_message];
+
+    let params = GenerationParams {
+        response_schema: Some(commit_message_schema()),
+        ..params.clone()
+    };
+    let response = llm.complete(&messages, &params).await?;
+
+    let commit_message = extract_json_where(&response, CommitMessage::is_complete)
+        .ok_or_else(|| {
+            AIPromptError(format!(
+                "the model did not answer with a commit message object: {}",
+                response.trim()
+            ))
+        })?;
+
+    Ok(commit_message.assemble())
+}
+
 pub struct GenerateCommand {
     pub git_entity: GitEntity,
 }
@@ -37,70 +191,29 @@ pub struct GenerateCommand {
 // use crate::{error::GitAIError, llm::LLMComplete};
 
 impl GenerateCommand {
-    pub fn get_ai_prompt(&self) -> Result<AIPrompt, AIPromptError> {
+    pub fn get_diff(&self) -> Result<&str, AIPromptError> {
         let GitEntity::Diff(Diff::WorkingTree { diff, .. }) = &self.git_entity else {
             return Err(AIPromptError(
                 "`draft` is only supported for working tree diffs".into(),
             ));
         };
 
-        let system_prompt = String::from(indoc! {"
-            You are a commit message generator that follows these rules:
-            1. Write in present tense
-            2. Be concise and direct
-            3. Output only the commit message without any explanations
-            4. Follow the format: <type>(<optional scope>): <commit message>
-        "});
-
-        let user_prompt = String::from(formatdoc! {"
-            Generate a concise git commit message written in present tense for the following code diff with the given specifications below:
-
-            The output response must be in format:
-            <type>(<optional scope>): <commit message>
-            Choose a type from the type-to-description JSON below that best describes the git diff:
-            {commit_types}
-            Focus on being accurate and concise.
-            Commit message must be a maximum of 72 characters.
-            Exclude anything unnecessary such as translation. Your entire response will be passed directly into git commit.
-
-            Code diff:
-            ```diff
-            {diff}
-            ```
-            ",
-            commit_types = default_commit_types(),
-        });
-
-        Ok(AIPrompt {
-            system_prompt,
-            user_prompt,
-        })
+        Ok(diff)
     }
 }
 
 #[async_trait]
 impl Command for GenerateCommand {
-    async fn execute(&self, llm: LLMProvider) -> Result<(), GitAIError> {
-        let ai_prompt = self.get_ai_prompt().unwrap();
+    async fn execute(&self, llm: LLMProvider, params: GenerationParams) -> Result<(), GitAIError> {
+        let diff = self.get_diff().unwrap();
 
-        let system_message = Message {
-            role: Role::System,
-            content: ai_prompt.system_prompt,
-        };
-
-        let user_message = Message {
-            role: Role::User,
-            content: ai_prompt.user_prompt,
-        };
-
-        let messages = vec![system_message, user_message];
-
-        let response = llm.complete(&messages).await;
+        let response = generate_commit_message(&llm, &params, diff).await;
 
         match response {
             Ok(commit_message) => {
                 println!("Commit message: {}", commit_message);
             }
+            Err(LLMError::DryRun) => return Err(LLMError::DryRun.into()),
             Err(e) => {
                 println!("Error: {}", GitAIError::from(e));
             }
@@ -109,3 +222,53 @@ impl Command for GenerateCommand {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assembles_the_header_body_and_footers() {
+        let message = CommitMessage {
+            commit_type: "feat".to_string(),
+            scope: Some(" api ".to_string()),
+            subject: "drop the v1 endpoints ".to_string(),
+            body: Some("They were deprecated a year ago.\n".to_string()),
+            breaking: true,
+            footers: vec![
+                "BREAKING CHANGE: /v1 is gone".to_string(),
+                " ".to_string(),
+                "Refs: #12".to_string(),
+            ],
+        };
+
+        assert_eq!(
+            message.assemble(),
+            "feat(api)!: drop the v1 endpoints\n\nThey were deprecated a year ago.\n\n\
+             BREAKING CHANGE: /v1 is gone\nRefs: #12"
+        );
+    }
+
+    #[test]
+    fn assembles_a_header_alone_without_empty_parts() {
+        let message = CommitMessage {
+            commit_type: "fix".to_string(),
+            scope: Some(String::new()),
+            subject: "handle empty diffs".to_string(),
+            body: Some(" ".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(message.assemble(), "fix: handle empty diffs");
+    }
+
+    #[test]
+    fn skips_an_incomplete_object_before_the_answer() {
+        let response = r#"Fields: {"type": "", "subject": ""}
+            Answer: {"type": "docs", "subject": "explain the cache"}"#;
+
+        let message = extract_json_where(response, CommitMessage::is_complete).unwrap();
+
+        assert_eq!(message.assemble(), "docs: explain the cache");
+    }
+}
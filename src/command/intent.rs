@@ -0,0 +1,232 @@
+use crate::llm::LLMComplete;
+
+use super::AIPrompt;
+use super::Command;
+use crate::config::GitAIConfig;
+use crate::git_entity::commit::Commit;
+use crate::git_entity::git_command::{GitCommand, GitCommandOutput, Safety};
+use crate::llm::{GenerationParams, LLMProvider, Message, Role};
+use crate::util::{extract_json, print_markdown};
+use dialoguer::{console::Term, theme::ColorfulTheme, Confirm, Input};
+use indoc::{formatdoc, indoc};
+use serde::Deserialize;
+use serde_json::json;
+use spinoff::{spinners, Color, Spinner};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::GitAIError;
+use async_trait::async_trait;
+
+#[derive(Deserialize)]
+struct ProposedCommand {
+    command: String,
+    explanation: String,
+}
+
+pub struct DoCommand {
+    pub intent: String,
+}
+
+impl DoCommand {
+    fn get_ai_prompt(&self) -> AIPrompt {
+        let system_prompt = String::from(indoc! {r#"
+            You are a Git expert that translates what the user wants to do into git commands.
+            1. Use only `git` commands, one invocation per command, without pipes, redirects or shell variables
+            2. Prefer the safest commands that achieve the intent
+            3. Output only a JSON array, in execution order, of objects like:
+               {"command": "git ...", "explanation": "what this command does"}
+        "#});
+
+        let status = GitCommand::parse("git status --short --branch")
+            .and_then(|command| command.run())
+            .map(|output| output.stdout)
+            .unwrap_or_default();
+
+        let user_prompt = formatdoc! {"
+            Current repository status:
+            ```
+            {status}```
+
+            What I want to do: {intent}
+            ",
+            intent = self.intent,
+        };
+
+        AIPrompt {
+            system_prompt,
+            user_prompt,
+        }
+    }
+
+    fn parse_proposals(response: &str) -> Option<Vec<ProposedCommand>> {
+        extract_json(response)
+    }
+
+    fn confirm(theme: &ColorfulTheme, command: &GitCommand) -> Result<bool, GitAIError> {
+        if command.is_destructive() {
+            println!("`{}` is destructive and may not be recoverable.", command.line);
+            let typed: String = Input::with_theme(theme)
+                .with_prompt("Type the command to run it, or leave empty to stop")
+                .allow_empty(true)
+                .interact_text_on(&Term::stderr())?;
+
+            return Ok(typed.trim() == command.line);
+        }
+
+        if command.classify() == Safety::RemoteOrDestructive {
+            println!("`{}` talks to a remote repository.", command.line);
+        }
+
+        Ok(Confirm::with_theme(theme)
+            .with_prompt(format!("Run `{}`?", command.line))
+            .default(false)
+            .interact_on(&Term::stderr())?)
+    }
+
+    fn log_execution(
+        &self,
+        command: &GitCommand,
+        head_before: &str,
+        output: &GitCommandOutput,
+    ) -> Result<(), GitAIError> {
+        let config_dir = GitAIConfig::get_config_dir()?;
+        fs::create_dir_all(&config_dir)?;
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+
+        let entry = json!({
+            "timestamp": timestamp,
+            "repository": std::env::current_dir()?,
+            "intent": self.intent,
+            "command": command.line,
+            "safety": command.classify().to_string(),
+            "head_before": head_before,
+            "head_after": Commit::get_full_hash("HEAD").unwrap_or_default(),
+            "success": output.success,
+            "stdout": output.stdout,
+            "stderr": output.stderr,
+        });
+
+        let mut log_file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(config_dir.join("do.log"))?;
+        writeln!(log_file, "{}", entry)?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Command for DoCommand {
+    async fn execute(&self, llm: LLMProvider, params: GenerationParams) -> Result<(), GitAIError> {
+        let mut spinner = Spinner::new(
+            spinners::Dots,
+            "Planning commands...".to_string(),
+            Color::Green,
+        );
+
+        let ai_prompt = self.get_ai_prompt();
+
+        let system_message = Message {
+            role: Role::System,
+            content: ai_prompt.system_prompt,
+        };
+
+        let user_message = Message {
+            role: Role::User,
+            content: ai_prompt.user_prompt,
+        };
+
+        let messages = vec![system_message, user_message];
+
+        let response = llm.complete(&messages, &params).await;
+
+        spinner.stop();
+
+        let Some(proposals) = Self::parse_proposals(&response?) else {
+            return Err(GitAIError::InvalidArguments(
+                "Could not understand the proposed commands".into(),
+            ));
+        };
+
+        if proposals.is_empty() {
+            println!("No commands proposed");
+            return Ok(());
+        }
+
+        // Refuse the whole plan if any step cannot be run safely on its own
+        let commands = proposals
+            .iter()
+            .map(|proposal| GitCommand::parse(&proposal.command))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        print_markdown(formatdoc! {"
+            # Proposed commands
+            {steps}
+            ",
+            steps = proposals
+                .iter()
+                .zip(&commands)
+                .enumerate()
+                .map(|(index, (proposal, command))| format!(
+                    "{}. `{}` *{}*\n   {}",
+                    index + 1,
+                    command.line,
+                    command.classify(),
+                    proposal.explanation
+                ))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        })?;
+
+        let theme = ColorfulTheme::default();
+        for command in &commands {
+            if !Self::confirm(&theme, command)? {
+                println!("Stopped, remaining commands were not run");
+                return Ok(());
+            }
+
+            let head_before = Commit::get_full_hash("HEAD").unwrap_or_default();
+            let output = command.run()?;
+            self.log_execution(command, &head_before, &output)?;
+
+            print!("{}", output.stdout);
+            eprint!("{}", output.stderr);
+
+            if !output.success {
+                println!("`{}` failed, remaining commands were not run", command.line);
+                return Ok(());
+            }
+        }
+
+        println!(
+            "Commands and their output were logged to {}",
+            GitAIConfig::get_config_dir()?.join("do.log").display()
+        );
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_proposals_among_prose() {
+        let response = "Here you go:\n```json\n[{\"command\": \"git log -n [5]\", \
+            \"explanation\": \"Shows [the] last commits\"}]\n```\nSee [the docs] for more.";
+
+        let proposals = DoCommand::parse_proposals(response).unwrap();
+
+        assert_eq!(proposals.len(), 1);
+        assert_eq!(proposals[0].command, "git log -n [5]");
+        assert!(DoCommand::parse_proposals("I cannot help with [that].").is_none());
+    }
+}
@@ -0,0 +1,272 @@
+use crate::llm::LLMComplete;
+
+use super::generate::commit_type_names;
+use super::AIPrompt;
+use super::Command;
+use crate::git_entity::commit::{Commit, CommitError};
+use crate::git_entity::diff::Diff;
+use crate::git_entity::hook::Hook;
+use crate::llm::{GenerationParams, LLMError, LLMProvider, Message, Role};
+use indoc::{formatdoc, indoc};
+use spinoff::{spinners, Color, Spinner, Streams};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::error::GitAIError;
+use async_trait::async_trait;
+
+const MAX_HEADER_LENGTH: usize = 72;
+
+/// Prefixes of messages written by git itself, which are not held to the convention
+const GENERATED_PREFIXES: [&str; 5] = ["Merge ", "Revert \"", "fixup! ", "squash! ", "amend! "];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Convention {
+    /// `<type>(<optional scope>)<optional !>: <subject>`, with the types used by `generate`
+    Conventional,
+    /// Only the header length and the blank line before the body are checked
+    Freeform,
+}
+
+impl Convention {
+    pub fn from_config(value: Option<&str>) -> Result<Self, GitAIError> {
+        match value.map(str::to_lowercase).as_deref() {
+            None | Some("conventional") => Ok(Convention::Conventional),
+            Some("freeform") => Ok(Convention::Freeform),
+            Some(other) => Err(GitAIError::ConfigError(format!(
+                "Unknown COMMIT_CONVENTION '{}', expected conventional or freeform",
+                other
+            ))),
+        }
+    }
+}
+
+pub enum LintTarget {
+    /// A message file, as passed to the `commit-msg` hook
+    File(PathBuf),
+    Commit(String),
+    InstallHook,
+}
+
+pub struct LintMessageCommand {
+    pub target: LintTarget,
+    pub convention: Convention,
+    pub check_diff: bool,
+}
+
+impl LintMessageCommand {
+    /// Drops the comments and the verbose diff git adds to the message file
+    fn clean_message(content: &str) -> String {
+        content
+            .lines()
+            .take_while(|line| !line.starts_with("# ------------------------ >8"))
+            .filter(|line| !line.starts_with('#'))
+            .collect::<Vec<_>>()
+            .join("\n")
+            .trim()
+            .to_string()
+    }
+
+    fn check_header(&self, header: &str, issues: &mut Vec<String>) {
+        if header.chars().count() > MAX_HEADER_LENGTH {
+            issues.push(format!(
+                "header is {} characters long, the maximum is {}",
+                header.chars().count(),
+                MAX_HEADER_LENGTH
+            ));
+        }
+
+        if self.convention == Convention::Freeform {
+            return;
+        }
+
+        let Some((prefix, subject)) = header.split_once(": ") else {
+            issues.push("header must follow `<type>(<optional scope>): <subject>`".into());
+            return;
+        };
+
+        let prefix = prefix.strip_suffix('!').unwrap_or(prefix);
+        let commit_type = match prefix.split_once('(') {
+            Some((commit_type, scope)) => {
+                if !scope.ends_with(')') || scope.len() < 2 {
+                    issues.push(format!("scope in `{}` is not closed or empty", prefix));
+                }
+                commit_type
+            }
+            None => prefix,
+        };
+
+        let commit_types = commit_type_names();
+        if !commit_types.iter().any(|t| t == commit_type) {
+            issues.push(format!(
+                "type `{}` is not one of: {}",
+                commit_type,
+                commit_types.join(", ")
+            ));
+        }
+
+        if subject.trim().is_empty() {
+            issues.push("subject is empty".into());
+        } else if subject.ends_with('.') {
+            issues.push("subject must not end with a period".into());
+        }
+    }
+
+    fn check_format(&self, message: &str) -> Vec<String> {
+        let mut issues = Vec::new();
+        let mut lines = message.lines();
+
+        let Some(header) = lines.next().filter(|header| !header.trim().is_empty()) else {
+            issues.push("message is empty".into());
+            return issues;
+        };
+
+        if GENERATED_PREFIXES
+            .iter()
+            .any(|prefix| header.starts_with(prefix))
+        {
+            return issues;
+        }
+
+        self.check_header(header, &mut issues);
+
+        if lines.next().is_some_and(|line| !line.trim().is_empty()) {
+            issues.push("header and body must be separated by a blank line".into());
+        }
+
+        issues
+    }
+
+    fn get_ai_prompt(message: &str, diff: &str) -> AIPrompt {
+        let system_prompt = String::from(indoc! {"
+            You are a code reviewer that checks whether a commit message describes its diff.
+            Minor omissions are fine, flag only messages that are misleading or describe different changes.
+            Answer with `MATCH`, or with `MISMATCH: <one sentence explaining the difference>`.
+        "});
+
+        let user_prompt = formatdoc! {"
+            Commit message:
+            {message}
+
+            Code diff:
+            ```diff
+            {diff}
+            ```
+            "
+        };
+
+        AIPrompt {
+            system_prompt,
+            user_prompt,
+        }
+    }
+
+    async fn check_diff(
+        &self,
+        llm: &LLMProvider,
+        params: &GenerationParams,
+        message: &str,
+        diff: &str,
+    ) -> Result<Option<String>, GitAIError> {
+        let mut spinner = Spinner::new_with_stream(
+            spinners::Dots,
+            "Comparing message and diff...".to_string(),
+            Color::Green,
+            Streams::Stderr,
+        );
+
+        let ai_prompt = Self::get_ai_prompt(message, diff);
+
+        let system_message = Message {
+            role: Role::System,
+            content: ai_prompt.system_prompt,
+        };
+
+        let user_message = Message {
+            role: Role::User,
+            content: ai_prompt.user_prompt,
+        };
+
+        let messages = vec![system_message, user_message];
+
+        let response = llm.complete(&messages, params).await;
+
+        spinner.stop();
+
+        match response {
+            Ok(response) => Ok(response
+                .trim()
+                .strip_prefix("MISMATCH")
+                .map(|reason| {
+                    format!(
+                        "message does not describe the diff: {}",
+                        reason.trim_start_matches(':').trim()
+                    )
+                })),
+            Err(LLMError::DryRun) => Err(LLMError::DryRun.into()),
+            // The format check is the contract, never block a commit on the model
+            Err(e) => {
+                eprintln!("Skipping the diff check: {}", GitAIError::from(e));
+                Ok(None)
+            }
+        }
+    }
+
+    fn install_hook(&self) -> Result<(), GitAIError> {
+        let hook = Hook::new("commit-msg")?;
+        let check_diff = if self.check_diff { " --check-diff" } else { "" };
+        hook.install(&format!("gitai lint-message \"$1\"{}", check_diff))?;
+
+        println!("Installed commit-msg hook at {}", hook.path.display());
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Command for LintMessageCommand {
+    async fn execute(&self, llm: LLMProvider, params: GenerationParams) -> Result<(), GitAIError> {
+        let (message, diff) = match &self.target {
+            LintTarget::InstallHook => return self.install_hook(),
+            LintTarget::File(path) => {
+                let message = Self::clean_message(&fs::read_to_string(path)?);
+                // Nothing staged happens with `commit --amend` of the message only
+                let diff = match Diff::from_working_tree(true) {
+                    Ok(Diff::WorkingTree { diff, .. }) => Some(diff),
+                    _ => None,
+                };
+                (message, diff)
+            }
+            LintTarget::Commit(sha) => match Commit::new(sha.clone()) {
+                Ok(commit) => (commit.message, Some(commit.diff)),
+                // An empty commit still has a message to check
+                Err(GitAIError::GitCommitError(CommitError::EmptyDiff(_))) => {
+                    (Commit::get_message(sha)?, None)
+                }
+                Err(e) => return Err(e),
+            },
+        };
+
+        let mut issues = self.check_format(&message);
+
+        if self.check_diff && issues.is_empty() {
+            match diff {
+                Some(diff) => {
+                    issues.extend(self.check_diff(&llm, &params, &message, &diff).await?)
+                }
+                None => eprintln!("Skipping the diff check: no changes to compare with"),
+            }
+        }
+
+        if !issues.is_empty() {
+            let report = issues
+                .iter()
+                .map(|issue| format!("  ✗ {}", issue))
+                .collect::<Vec<_>>()
+                .join("\n");
+            return Err(GitAIError::InvalidCommitMessage(report));
+        }
+
+        println!("✓ Commit message looks good");
+        Ok(())
+    }
+}
@@ -1,13 +1,34 @@
-use crate::{error::GitAIError, git_entity::diff::Diff, llm::LLMProvider};
+use crate::{error::GitAIError, git_entity::diff::Diff, llm::{get_llm, LLMProvider}};
+use crate::llm::GenerationParams;
+use crate::cli::{CacheAction, LLMProviderType};
+use crate::config::GitAIConfig;
+use crate::llm::cache::ResponseCache;
 use async_trait::async_trait;
+use std::path::PathBuf;
 use crate::git_entity::GitEntity;
+use crate::git_entity::log::LogQuery;
 
 mod generate;
 mod explain;
 mod configure;
+mod branch;
+mod reword;
+mod conflicts;
+mod squash_message;
+mod ask;
+mod intent;
+mod standup;
+mod lint_message;
+mod version_bump;
+mod cache;
+mod models;
+
+pub use branch::{BranchSource, DEFAULT_BRANCH_PATTERN};
+pub use lint_message::{Convention, LintTarget};
+pub use squash_message::SquashSource;
 
 pub struct GitAICommand {
-    provider: LLMProvider,
+    config: GitAIConfig,
 }
 
 pub struct AIPrompt {
@@ -16,37 +37,214 @@ pub struct AIPrompt {
 }
 
 impl GitAICommand {
-    pub fn new(provider: LLMProvider) -> Self {
-        Self { provider }
+    pub fn new(config: GitAIConfig) -> Self {
+        Self { config }
+    }
+
+    /// The command's own settings win over the global ones, which win over the built-in defaults
+    fn generation_params(&self, command_type: &CommandType) -> GenerationParams {
+        self.config
+            .command_generation
+            .get(command_type.name())
+            .cloned()
+            .unwrap_or_default()
+            .or(&self.config.generation)
+            .or_builtin(&command_type.default_generation_params())
     }
 
-    pub async fn execute(&self, command_type: CommandType) -> Result<(), GitAIError> {
-        command_type
-            .create_command()?
-            .execute(self.provider.clone())
-            .await
+    pub async fn execute(&self, mut command_type: CommandType) -> Result<(), GitAIError> {
+        let params = self.generation_params(&command_type);
+        // Built only when needed, so a provider without a key or with a broken setting does
+        // not stop the commit-msg hook, nor `configure` from fixing it
+        let llm = if command_type.uses_llm() {
+            match get_llm(&self.config, command_type.caches_responses()) {
+                Ok(llm) => llm,
+                // The format check is the contract of the hook, it runs without the model
+                Err(e) => match &mut command_type {
+                    CommandType::LintMessage { check_diff, .. } => {
+                        eprintln!("Skipping the diff check: {}", e);
+                        *check_diff = false;
+                        LLMProvider::Local
+                    }
+                    _ => return Err(e),
+                },
+            }
+        } else {
+            LLMProvider::Local
+        };
+        command_type.create_command()?.execute(llm, params).await
     }
 }
 
 pub enum CommandType {
     Generate,
     Explain { git_entity: GitEntity },
-    Configure,
+    Configure {
+        /// The current settings, whose network settings are used to list the models
+        config: Box<GitAIConfig>,
+    },
+    Branch {
+        source: BranchSource,
+        ticket: Option<String>,
+        pattern: String,
+        count: usize,
+    },
+    Reword {
+        from: String,
+        to: String,
+        force: bool,
+    },
+    Conflicts,
+    SquashMessage {
+        source: SquashSource,
+    },
+    Ask {
+        question: String,
+        constraints: LogQuery,
+    },
+    Do {
+        intent: String,
+    },
+    Standup {
+        since: String,
+        author: String,
+        repos: Vec<PathBuf>,
+    },
+    LintMessage {
+        target: LintTarget,
+        convention: Convention,
+        check_diff: bool,
+    },
+    VersionBump {
+        create_tag: bool,
+    },
+    Cache {
+        action: CacheAction,
+        cache: ResponseCache,
+    },
+    Models {
+        provider: LLMProviderType,
+        model: Option<String>,
+    },
 }
 
 impl CommandType {
+    /// The command's name on the command line, which prefixes its settings in the config
+    pub fn name(&self) -> &'static str {
+        match self {
+            CommandType::Generate => "generate",
+            CommandType::Explain { .. } => "explain",
+            CommandType::Configure { .. } => "configure",
+            CommandType::Branch { .. } => "branch",
+            CommandType::Reword { .. } => "reword",
+            CommandType::Conflicts => "conflicts",
+            CommandType::SquashMessage { .. } => "squash-message",
+            CommandType::Ask { .. } => "ask",
+            CommandType::Do { .. } => "do",
+            CommandType::Standup { .. } => "standup",
+            CommandType::LintMessage { .. } => "lint-message",
+            CommandType::VersionBump { .. } => "version-bump",
+            CommandType::Cache { .. } => "cache",
+            CommandType::Models { .. } => "models",
+        }
+    }
+
+    /// Whether the command sends requests to the provider
+    fn uses_llm(&self) -> bool {
+        match self {
+            CommandType::Configure { .. } | CommandType::Cache { .. } => false,
+            CommandType::LintMessage { check_diff, .. } => *check_diff,
+            _ => true,
+        }
+    }
+
+    /// Whether a repeated prompt may be answered from the cache. Only answers about a fixed
+    /// input are, a command run again for other suggestions must reach the model
+    fn caches_responses(&self) -> bool {
+        matches!(self, CommandType::Explain { .. } | CommandType::Ask { .. })
+    }
+
+    /// Commit messages should not vary between runs, explanations read better with some variety
+    fn default_generation_params(&self) -> GenerationParams {
+        let temperature = match self {
+            CommandType::Generate => Some(0.2),
+            CommandType::Explain { .. } => Some(0.7),
+            _ => None,
+        };
+
+        GenerationParams {
+            temperature,
+            ..Default::default()
+        }
+    }
+
     pub fn create_command(self) -> Result<Box<dyn Command>, GitAIError> {
         match self {
             CommandType::Generate => Ok(Box::new(generate::GenerateCommand {
                 git_entity: GitEntity::Diff(Diff::from_working_tree(true).unwrap()),
             })),
             CommandType::Explain { git_entity } => Ok(Box::new(explain::ExplainCommand { git_entity })),
-            CommandType::Configure => Ok(Box::new(configure::ConfigureCommand {})),
+            CommandType::Configure { config } => {
+                Ok(Box::new(configure::ConfigureCommand { config: *config }))
+            }
+            CommandType::Branch {
+                source,
+                ticket,
+                pattern,
+                count,
+            } => Ok(Box::new(branch::BranchCommand {
+                source,
+                ticket,
+                pattern,
+                count,
+            })),
+            CommandType::Reword { from, to, force } => {
+                Ok(Box::new(reword::RewordCommand { from, to, force }))
+            }
+            CommandType::Conflicts => Ok(Box::new(conflicts::ConflictsCommand {})),
+            CommandType::SquashMessage { source } => {
+                Ok(Box::new(squash_message::SquashMessageCommand { source }))
+            }
+            CommandType::Ask {
+                question,
+                constraints,
+            } => Ok(Box::new(ask::AskCommand {
+                question,
+                constraints,
+            })),
+            CommandType::Do { intent } => Ok(Box::new(intent::DoCommand { intent })),
+            CommandType::Standup {
+                since,
+                author,
+                repos,
+            } => Ok(Box::new(standup::StandupCommand {
+                since,
+                author,
+                repos,
+            })),
+            CommandType::LintMessage {
+                target,
+                convention,
+                check_diff,
+            } => Ok(Box::new(lint_message::LintMessageCommand {
+                target,
+                convention,
+                check_diff,
+            })),
+            CommandType::VersionBump { create_tag } => {
+                Ok(Box::new(version_bump::VersionBumpCommand { create_tag }))
+            }
+            CommandType::Cache { action, cache } => {
+                Ok(Box::new(cache::CacheCommand { action, cache }))
+            }
+            CommandType::Models { provider, model } => Ok(Box::new(models::ModelsCommand {
+                configured: model.unwrap_or_else(|| provider.default_model().to_string()),
+            })),
         }
     }
 }
 
 #[async_trait]
 pub trait Command {
-    async fn execute(&self, llm: LLMProvider) -> Result<(), GitAIError>;
+    async fn execute(&self, llm: LLMProvider, params: GenerationParams) -> Result<(), GitAIError>;
 }
@@ -0,0 +1,59 @@
+use super::Command;
+use crate::llm::{same_model, GenerationParams, LLMComplete, LLMProvider, ModelInfo};
+use spinoff::{spinners, Color, Spinner};
+
+use crate::error::GitAIError;
+use async_trait::async_trait;
+
+pub struct ModelsCommand {
+    /// The configured model, or the provider's default one
+    pub configured: String,
+}
+
+fn format_context_window(model: &ModelInfo) -> String {
+    match model.context_window {
+        Some(tokens) => format!("{} tokens", tokens),
+        None => String::new(),
+    }
+}
+
+#[async_trait]
+impl Command for ModelsCommand {
+    async fn execute(
+        &self,
+        llm: LLMProvider,
+        _params: GenerationParams,
+    ) -> Result<(), GitAIError> {
+        let mut spinner = Spinner::new(
+            spinners::Dots,
+            "Fetching models...".to_string(),
+            Color::Green,
+        );
+
+        let models = llm.list_models().await;
+
+        spinner.stop();
+
+        let mut models = models?;
+        models.sort_by(|a, b| a.id.cmp(&b.id));
+
+        let width = models.iter().map(|model| model.id.len()).max().unwrap_or_default();
+        for model in &models {
+            let marker = if same_model(&model.id, &self.configured) { "*" } else { " " };
+            let line = format!(
+                "{} {:<width$}  {}",
+                marker,
+                model.id,
+                format_context_window(model),
+                width = width
+            );
+            println!("{}", line.trim_end());
+        }
+
+        if !models.iter().any(|model| same_model(&model.id, &self.configured)) {
+            println!("\nThe configured model, {}, is not in the list", self.configured);
+        }
+
+        Ok(())
+    }
+}
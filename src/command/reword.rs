@@ -0,0 +1,214 @@
+use super::generate::generate_commit_message;
+use super::Command;
+use crate::git_entity::commit::{Commit, CommitError};
+use crate::git_entity::rebase::{reword_commits, Reword};
+use crate::llm::{GenerationParams, LLMError, LLMProvider};
+use crate::util::print_markdown;
+use dialoguer::{console::Term, theme::ColorfulTheme, Confirm};
+use indoc::formatdoc;
+use spinoff::{spinners, Color, Spinner};
+
+use crate::error::GitAIError;
+use async_trait::async_trait;
+
+pub struct RewordCommand {
+    pub from: String,
+    pub to: String,
+    pub force: bool,
+}
+
+impl RewordCommand {
+    /// Every commit of the range, oldest first, and the ones with changes to describe;
+    /// empty commits keep their message
+    fn collect_commits(&self) -> Result<(Vec<String>, Vec<Commit>), GitAIError> {
+        Commit::is_valid_commit(&self.from)?;
+        Commit::is_valid_commit(&self.to)?;
+
+        if Commit::get_full_hash(&self.to)? != Commit::get_full_hash("HEAD")? {
+            return Err(GitAIError::InvalidArguments(
+                "`reword` can only rewrite a range that ends at HEAD".into(),
+            ));
+        }
+
+        let hashes = Commit::list_range(&self.from, &self.to)?;
+        if hashes.is_empty() {
+            return Err(GitAIError::InvalidArguments(format!(
+                "No commits in range {}..{}",
+                self.from, self.to
+            )));
+        }
+
+        for hash in &hashes {
+            if Commit::is_merge(hash)? {
+                return Err(GitAIError::InvalidArguments(format!(
+                    "Range contains merge commit {}, which `reword` cannot rewrite",
+                    hash
+                )));
+            }
+
+            if !self.force && Commit::is_pushed(hash)? {
+                return Err(GitAIError::InvalidArguments(format!(
+                    "Commit {} is already pushed, use --force to rewrite it anyway",
+                    hash
+                )));
+            }
+        }
+
+        let mut commits = Vec::new();
+        for hash in &hashes {
+            match Commit::new(hash.clone()) {
+                Ok(commit) => commits.push(commit),
+                Err(GitAIError::GitCommitError(CommitError::EmptyDiff(_))) => {
+                    println!("Commit {} has no changes, keeping its message", &hash[..7]);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok((hashes, commits))
+    }
+
+    /// The subjects side by side, then both full messages, which do not fit in a table cell
+    fn format_comparison(commit: &Commit, new_message: &str) -> String {
+        let subject = |message: &str| {
+            message
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .replace('|', "\\|")
+        };
+
+        formatdoc! {"
+            |:-:|:-|:-|
+            |**commit**|**old**|**new**|
+            |-|-|-|
+            |`{hash}`|{old_subject}|{new_subject}|
+            |-|-|-|
+
+            **old**
+            {old}
+
+            **new**
+            {new}
+            ",
+            hash = &commit.full_hash[..7],
+            old_subject = subject(&commit.message),
+            new_subject = subject(new_message),
+            old = fenced(commit.message.trim_end()),
+            new = fenced(new_message.trim_end()),
+        }
+    }
+
+    async fn generate_message(
+        &self,
+        llm: &LLMProvider,
+        params: &GenerationParams,
+        commit: &Commit,
+    ) -> Result<String, GitAIError> {
+        Ok(generate_commit_message(llm, params, &commit.diff).await?)
+    }
+}
+
+/// A code block longer than any run of backticks in the text, which is shown as it is
+fn fenced(text: &str) -> String {
+    let longest_run = text
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or_default();
+    let fence = "`".repeat(longest_run.max(2) + 1);
+    format!("{}\n{}\n{}", fence, text, fence)
+}
+
+#[async_trait]
+impl Command for RewordCommand {
+    async fn execute(&self, llm: LLMProvider, params: GenerationParams) -> Result<(), GitAIError> {
+        let (hashes, commits) = self.collect_commits()?;
+        let theme = ColorfulTheme::default();
+
+        let mut rewords = Vec::new();
+        for commit in &commits {
+            let mut spinner = Spinner::new(
+                spinners::Dots,
+                format!("Generating message for {}...", &commit.full_hash[..7]),
+                Color::Green,
+            );
+
+            let response = self.generate_message(&llm, &params, commit).await;
+
+            spinner.stop();
+
+            let new_message = match response {
+                Ok(new_message) if !new_message.is_empty() => new_message,
+                Ok(_) => {
+                    println!("Empty message generated, keeping the original");
+                    continue;
+                }
+                Err(GitAIError::LLMError(LLMError::DryRun)) => {
+                    return Err(LLMError::DryRun.into())
+                }
+                Err(e) => {
+                    println!("Error: {}", e);
+                    continue;
+                }
+            };
+
+            print_markdown(Self::format_comparison(commit, &new_message))?;
+
+            let accept = Confirm::with_theme(&theme)
+                .with_prompt("Use the new message?")
+                .default(true)
+                .interact_on(&Term::stderr())?;
+
+            if accept {
+                rewords.push(Reword {
+                    full_hash: commit.full_hash.clone(),
+                    message: new_message,
+                });
+            }
+        }
+
+        if rewords.is_empty() {
+            println!("Nothing to reword");
+            return Ok(());
+        }
+
+        // Rewording must not rebase the branch onto a `from` that moved on since it forked
+        let base = Commit::merge_base(&self.from, &self.to)?;
+        reword_commits(&base, &hashes, &rewords)?;
+
+        println!("Reworded {} of {} commits", rewords.len(), hashes.len());
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shows_multi_line_messages_below_the_subjects() {
+        let commit = Commit {
+            full_hash: "0123456789abcdef".to_string(),
+            message: "Fix retries\n\nOld body\n".to_string(),
+            diff: String::new(),
+            author_name: String::new(),
+            author_email: String::new(),
+            date: String::new(),
+        };
+        let new_message = "fix(http): retry timeouts | resets\n\nUse `is_timeout`\n\nRefs: #12";
+
+        let comparison = RewordCommand::format_comparison(&commit, new_message);
+
+        let rows = comparison.lines().filter(|line| line.starts_with('|')).collect::<Vec<_>>();
+        assert_eq!(rows[3], "|`0123456`|Fix retries|fix(http): retry timeouts \\| resets|");
+        assert!(comparison.contains("```\nFix retries\n\nOld body\n```"));
+        assert!(comparison.contains(&format!("```\n{}\n```", new_message)));
+    }
+
+    #[test]
+    fn fences_a_message_longer_than_its_backticks() {
+        assert_eq!(fenced("a ``` b"), "````\na ``` b\n````");
+    }
+}
@@ -0,0 +1,291 @@
+use crate::llm::LLMComplete;
+
+use super::generate::default_commit_types;
+use super::AIPrompt;
+use super::Command;
+use crate::git_entity::commit::Commit;
+use crate::git_entity::diff::Diff;
+use crate::llm::{GenerationParams, LLMError, LLMProvider, Message, Role};
+use indoc::{formatdoc, indoc};
+use spinoff::{spinners, Color, Spinner, Streams};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use crate::error::GitAIError;
+use async_trait::async_trait;
+
+pub enum SquashSource {
+    Range { from: String, to: String },
+    /// The message file git hands to `GIT_EDITOR` during a squash step of `git rebase -i`
+    EditorFile(PathBuf),
+}
+
+pub struct SquashMessageCommand {
+    pub source: SquashSource,
+}
+
+impl SquashMessageCommand {
+    fn get_ai_prompt(messages: &[String], diff: &str) -> AIPrompt {
+        let system_prompt = String::from(indoc! {"
+            You are a commit message generator that combines several commits into one.
+            1. Write in present tense
+            2. Output only the commit message without any explanations
+            3. The first line follows the format: <type>(<optional scope>): <commit message>
+            4. After a blank line, the body lists the notable sub-changes as `- ` bullet points
+        "});
+
+        let messages = messages
+            .iter()
+            .enumerate()
+            .map(|(index, message)| format!("Commit {}:\n{}", index + 1, message.trim()))
+            .collect::<Vec<_>>()
+            .join("\n\n");
+
+        let user_prompt = formatdoc! {"
+            Generate a single commit message for the squash of the commits below.
+
+            Choose a type from the type-to-description JSON below that best describes the combined change:
+            {commit_types}
+            The first line must be a maximum of 72 characters.
+            Leave out sub-changes that are undone or superseded by later commits, such as fixups and typo fixes.
+            Your entire response will be passed directly into git commit.
+
+            Messages of the squashed commits:
+            {messages}
+
+            Net code diff:
+            ```diff
+            {diff}
+            ```
+            ",
+            commit_types = default_commit_types(),
+        };
+
+        AIPrompt {
+            system_prompt,
+            user_prompt,
+        }
+    }
+
+    /// Pulls the individual messages out of git's "This is a combination of N commits" template,
+    /// whose comments start with `comment`
+    fn parse_squash_template(content: &str, comment: &str) -> Option<Vec<String>> {
+        if !content.starts_with(&format!("{} This is a combination of", comment)) {
+            return None;
+        }
+
+        let mut messages: Vec<String> = Vec::new();
+        for line in content.lines() {
+            let Some(comment_text) = line.strip_prefix(comment) else {
+                if let Some(message) = messages.last_mut() {
+                    message.push_str(line);
+                    message.push('\n');
+                }
+                continue;
+            };
+            if comment_text.starts_with(" This is the") && comment_text.contains("commit message") {
+                messages.push(String::new());
+            }
+        }
+
+        messages.retain(|message| !message.trim().is_empty());
+        Some(messages)
+    }
+
+    /// The prefix of git's comment lines, `core.commentChar` or, when git picks one itself,
+    /// the one the template starts with
+    fn comment_prefix(content: &str) -> String {
+        let configured = process::Command::new("git")
+            .args(["config", "--get", "core.commentChar"])
+            .output()
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+            .unwrap_or_default();
+
+        match configured.as_str() {
+            "" => "#".to_string(),
+            "auto" => content
+                .lines()
+                .next()
+                .and_then(|line| line.split_once(" This is a combination of"))
+                .map_or("#".to_string(), |(prefix, _)| prefix.to_string()),
+            _ => configured,
+        }
+    }
+
+    fn collect_range(from: &str, to: &str) -> Result<(Vec<String>, String), GitAIError> {
+        // Read without diffs, an empty commit in the range has a message all the same
+        let messages = Commit::list_messages(&format!("{}..{}", from, to))?
+            .into_iter()
+            .rev()
+            .map(|(_, message)| message)
+            .collect();
+
+        let Diff::CommitsRange { diff, .. } = Diff::from_commits_range(from, to, false)? else {
+            unreachable!()
+        };
+
+        Ok((messages, diff))
+    }
+
+    fn collect_editor_file(messages: Vec<String>) -> Result<(Vec<String>, String), GitAIError> {
+        // The squash is being amended onto HEAD, so the index holds the combined tree
+        let Diff::WorkingTree { diff, .. } = Diff::from_index("HEAD~1")? else {
+            unreachable!()
+        };
+
+        Ok((messages, diff))
+    }
+
+    /// Opens the file in the editor git would use without gitai, for the todo list and the
+    /// reword and edit steps of the rebase
+    fn open_in_editor(path: &Path) -> Result<(), GitAIError> {
+        let output = process::Command::new("git")
+            .args(["var", "GIT_EDITOR"])
+            .env_remove("GIT_EDITOR")
+            .output()?;
+        let editor = String::from_utf8(output.stdout)?.trim().to_string();
+
+        // Set as core.editor too, it would only call itself again
+        if editor.is_empty() || editor.contains("squash-message") {
+            return Ok(());
+        }
+
+        // Run through the shell like git does, the editor may come with arguments
+        let status = process::Command::new("sh")
+            .arg("-c")
+            .arg(format!("{} \"$@\"", editor))
+            .arg(&editor)
+            .arg(path)
+            .status()?;
+        if !status.success() {
+            let message = format!("The editor '{}' exited with {}", editor, status);
+            return Err(io::Error::other(message).into());
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Command for SquashMessageCommand {
+    async fn execute(&self, llm: LLMProvider, params: GenerationParams) -> Result<(), GitAIError> {
+        let (messages, diff) = match &self.source {
+            SquashSource::Range { from, to } => Self::collect_range(from, to)?,
+            SquashSource::EditorFile(path) => {
+                let content = fs::read_to_string(path)?;
+                let Some(messages) =
+                    Self::parse_squash_template(&content, &Self::comment_prefix(&content))
+                else {
+                    // The todo list, a reword or an edit step
+                    return Self::open_in_editor(path);
+                };
+
+                match Self::collect_editor_file(messages) {
+                    Ok(collected) => collected,
+                    // Failing here would make git abort the rebase step, keep its message
+                    Err(e) => {
+                        eprintln!("Keeping the default squash message: {}", e);
+                        return Ok(());
+                    }
+                }
+            }
+        };
+
+        let mut spinner = Spinner::new_with_stream(
+            spinners::Dots,
+            "Generating squash message...".to_string(),
+            Color::Green,
+            Streams::Stderr,
+        );
+
+        let ai_prompt = Self::get_ai_prompt(&messages, &diff);
+
+        let system_message = Message {
+            role: Role::System,
+            content: ai_prompt.system_prompt,
+        };
+
+        let user_message = Message {
+            role: Role::User,
+            content: ai_prompt.user_prompt,
+        };
+
+        let messages = vec![system_message, user_message];
+
+        let response = llm.complete(&messages, &params).await;
+
+        spinner.stop();
+
+        let commit_message = match (response, &self.source) {
+            (Ok(response), _) => response.trim().to_string(),
+            (Err(LLMError::DryRun), _) => return Err(LLMError::DryRun.into()),
+            // Failing here would make git abort the rebase step, fall back to its message
+            (Err(e), SquashSource::EditorFile(_)) => {
+                eprintln!("Keeping the default squash message: {}", GitAIError::from(e));
+                return Ok(());
+            }
+            (Err(e), SquashSource::Range { .. }) => return Err(e.into()),
+        };
+
+        match &self.source {
+            SquashSource::Range { .. } => println!("{}", commit_message),
+            SquashSource::EditorFile(path) => {
+                // Keep git's combined message below as a comment for reference
+                let original = fs::read_to_string(path)?;
+                let comment = Self::comment_prefix(&original);
+                let commented = original
+                    .lines()
+                    .map(|line| {
+                        if line.starts_with(&comment) {
+                            line.to_string()
+                        } else {
+                            format!("{} {}", comment, line)
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n");
+
+                fs::write(path, format!("{}\n\n{}\n", commit_message, commented))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_messages_of_a_squash_template() {
+        let template = "# This is a combination of 3 commits.\n# This is the 1st commit message:\n\n\
+            Add retries\n\nWith a backoff\n\n# This is the commit message #2:\n\nfixup! Add retries\n\n\
+            # This is the commit message #3:\n\n# Please enter the commit message for your changes.\n";
+
+        assert_eq!(
+            SquashMessageCommand::parse_squash_template(template, "#").unwrap(),
+            ["\nAdd retries\n\nWith a backoff\n\n", "\nfixup! Add retries\n\n"]
+        );
+    }
+
+    #[test]
+    fn reads_a_template_with_another_comment_char() {
+        let template = "; This is a combination of 2 commits.\n; This is the 1st commit message:\n\n\
+            # Heading kept\n\n; The commit message #2 will be skipped:\n\n; typo\n";
+
+        assert_eq!(
+            SquashMessageCommand::parse_squash_template(template, ";").unwrap(),
+            ["\n# Heading kept\n\n\n"]
+        );
+    }
+
+    #[test]
+    fn leaves_other_files_to_the_editor() {
+        let todo = "pick 0123456 Add retries\nsquash 89abcde fixup\n\n# Rebase 0123..89ab onto 0123\n";
+
+        assert!(SquashMessageCommand::parse_squash_template(todo, "#").is_none());
+        assert!(SquashMessageCommand::parse_squash_template("Fix typo\n", "#").is_none());
+    }
+}
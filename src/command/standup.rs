@@ -0,0 +1,279 @@
+use crate::llm::LLMComplete;
+
+use super::AIPrompt;
+use super::Command;
+use crate::llm::{GenerationParams, LLMError, LLMProvider, Message, Role};
+use crate::util::print_markdown;
+use indoc::{formatdoc, indoc};
+use spinoff::{spinners, Color, Spinner};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use crate::error::GitAIError;
+use async_trait::async_trait;
+
+const MAX_COMMITS_PER_REPO: usize = 50;
+
+pub struct StandupCommand {
+    pub since: String,
+    pub author: String,
+    pub repos: Vec<PathBuf>,
+}
+
+/// What the note needs of a commit, read in one `git log` instead of a diff per commit
+struct StandupCommit {
+    hash: String,
+    date: String,
+    message: String,
+}
+
+struct RepoActivity {
+    name: String,
+    commits: Vec<StandupCommit>,
+    status: String,
+}
+
+impl RepoActivity {
+    /// Uncommitted work, below the `## <branch>` line of the short status
+    fn has_changes(&self) -> bool {
+        self.status.lines().any(|line| !line.starts_with("##"))
+    }
+}
+
+/// `--author` takes a basic regular expression, where the dots of an email match any character
+fn escape_pattern(text: &str) -> String {
+    let mut pattern = String::new();
+    for c in text.chars() {
+        if "\\.[]*^$".contains(c) {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern
+}
+
+impl StandupCommand {
+    /// Runs git in `repo` with `-C`, so that no repository changes the process directory
+    fn run_git(repo: &Path, args: &[&str]) -> Result<process::Output, GitAIError> {
+        Ok(process::Command::new("git")
+            .arg("-C")
+            .arg(repo)
+            .args(args)
+            .output()?)
+    }
+
+    fn git_stdout(repo: &Path, args: &[&str]) -> Result<String, GitAIError> {
+        let output = Self::run_git(repo, args)?;
+        Ok(String::from_utf8(output.stdout)?.trim().to_string())
+    }
+
+    fn get_author(&self, repo: &Path, name: &str) -> Result<String, GitAIError> {
+        if self.author != "me" {
+            return Ok(self.author.clone());
+        }
+
+        // An empty `--author=` would match every author
+        let email = Self::git_stdout(repo, &["config", "user.email"])?;
+        if email.is_empty() {
+            return Err(GitAIError::InvalidArguments(format!(
+                "No user.email configured in '{}', use --author",
+                name
+            )));
+        }
+        Ok(escape_pattern(&email))
+    }
+
+    fn get_commits(&self, repo: &Path, author: &str) -> Result<Vec<StandupCommit>, GitAIError> {
+        let output = Self::git_stdout(
+            repo,
+            &[
+                "log",
+                "--all",
+                "--no-merges",
+                "--basic-regexp",
+                "-n",
+                &MAX_COMMITS_PER_REPO.to_string(),
+                &format!("--author={}", author),
+                &format!("--since={}", self.since),
+                "--date=format:%Y-%m-%d %H:%M:%S",
+                "--format=%h%x1f%cd%x1f%B%x1e",
+            ],
+        )?;
+
+        Ok(output
+            .split('\x1e')
+            .filter_map(|record| {
+                let mut fields = record.trim().splitn(3, '\x1f');
+                Some(StandupCommit {
+                    hash: fields.next().filter(|hash| !hash.is_empty())?.to_string(),
+                    date: fields.next()?.to_string(),
+                    message: fields.next()?.trim().to_string(),
+                })
+            })
+            .collect())
+    }
+
+    fn collect_activity(&self, repo: &Path) -> Result<RepoActivity, GitAIError> {
+        let name = repo
+            .canonicalize()?
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| repo.display().to_string());
+
+        if !Self::run_git(repo, &["rev-parse", "--git-dir"])?.status.success() {
+            return Err(GitAIError::InvalidArguments(format!(
+                "'{}' is not a git repository",
+                name
+            )));
+        }
+
+        let author = self.get_author(repo, &name)?;
+        let commits = self.get_commits(repo, &author)?;
+        let status = Self::git_stdout(repo, &["status", "--short", "--branch"])?;
+
+        Ok(RepoActivity {
+            name,
+            commits,
+            status,
+        })
+    }
+
+    fn get_ai_prompt(&self, activities: &[RepoActivity]) -> AIPrompt {
+        let system_prompt = String::from(indoc! {"
+            You are a helpful assistant that writes daily standup notes from Git activity.
+            Write a short markdown note with exactly three sections: Did, Doing, Blockers.
+            - Did: what was completed, grouped by repository, based on the commits
+            - Doing: work in progress, based on uncommitted changes and unmerged branches
+            - Blockers: only what the activity clearly shows (e.g. conflicts), otherwise write None
+            Describe the work in plain language instead of repeating commit messages verbatim.
+        "});
+
+        let repositories = activities
+            .iter()
+            .map(|activity| {
+                let commits = if activity.commits.is_empty() {
+                    "(no commits)".to_string()
+                } else {
+                    activity
+                        .commits
+                        .iter()
+                        .map(|commit| {
+                            format!(
+                                "- {} {}: {}",
+                                commit.hash,
+                                commit.date,
+                                commit.message.replace('\n', "\n  ")
+                            )
+                        })
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                };
+
+                formatdoc! {"
+                    Repository: {name}
+                    Commits:
+                    {commits}
+                    Working tree status:
+                    ```
+                    {status}
+                    ```
+                    ",
+                    name = activity.name,
+                    status = activity.status,
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let user_prompt = formatdoc! {"
+            Write my standup note for the activity since {since}.
+
+            {repositories}
+            ",
+            since = self.since,
+        };
+
+        AIPrompt {
+            system_prompt,
+            user_prompt,
+        }
+    }
+}
+
+#[async_trait]
+impl Command for StandupCommand {
+    async fn execute(&self, llm: LLMProvider, params: GenerationParams) -> Result<(), GitAIError> {
+        let activities = self
+            .repos
+            .iter()
+            .map(|repo| self.collect_activity(repo))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if activities
+            .iter()
+            .all(|activity| activity.commits.is_empty() && !activity.has_changes())
+        {
+            println!("No commits or uncommitted changes found since {}", self.since);
+            return Ok(());
+        }
+
+        let mut spinner = Spinner::new(
+            spinners::Dots,
+            "Writing standup note...".to_string(),
+            Color::Green,
+        );
+
+        let ai_prompt = self.get_ai_prompt(&activities);
+
+        let system_message = Message {
+            role: Role::System,
+            content: ai_prompt.system_prompt,
+        };
+
+        let user_message = Message {
+            role: Role::User,
+            content: ai_prompt.user_prompt,
+        };
+
+        let messages = vec![system_message, user_message];
+
+        let response = llm.complete(&messages, &params).await;
+
+        spinner.stop();
+
+        match response {
+            Ok(response) => {
+                print_markdown(response)?;
+            }
+            Err(LLMError::DryRun) => return Err(LLMError::DryRun.into()),
+            Err(e) => {
+                println!("Error: {}", GitAIError::from(e));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_the_email_for_git_log() {
+        assert_eq!(escape_pattern("jane.doe+git@example.com"), "jane\\.doe+git@example\\.com");
+        assert_eq!(escape_pattern("a*b[c]^$\\"), "a\\*b\\[c\\]\\^\\$\\\\");
+    }
+
+    #[test]
+    fn counts_uncommitted_work_as_activity() {
+        let activity = |status: &str| RepoActivity {
+            name: "api".to_string(),
+            commits: Vec::new(),
+            status: status.to_string(),
+        };
+
+        assert!(!activity("## main...origin/main").has_changes());
+        assert!(activity("## main\n M src/lib.rs\n?? notes.md").has_changes());
+    }
+}
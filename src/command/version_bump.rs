@@ -0,0 +1,386 @@
+use crate::llm::LLMComplete;
+
+use super::AIPrompt;
+use super::Command;
+use crate::git_entity::commit::Commit;
+use crate::git_entity::diff::Diff;
+use crate::git_entity::tag::{Bump, Tag, Version};
+use crate::llm::{GenerationParams, LLMError, LLMProvider, Message, Role};
+use crate::util::print_markdown;
+use dialoguer::{console::Term, theme::ColorfulTheme, Confirm};
+use indoc::{formatdoc, indoc};
+use spinoff::{spinners, Color, Spinner};
+use std::collections::HashSet;
+
+use crate::error::GitAIError;
+use async_trait::async_trait;
+
+const RUST_PUBLIC_ITEMS: [&str; 10] = [
+    "pub fn ",
+    "pub async fn ",
+    "pub unsafe fn ",
+    "pub struct ",
+    "pub enum ",
+    "pub trait ",
+    "pub type ",
+    "pub const ",
+    "pub static ",
+    "pub mod ",
+];
+
+const CLI_FLAG_MARKERS: [&str; 6] = [
+    "#[arg(",
+    "#[clap(",
+    "Arg::new(",
+    ".long(\"",
+    "add_argument(\"--",
+    ".option('--",
+];
+
+/// Commits listed in the prompts, the newest ones; the bump declared by the older ones still
+/// counts
+const MAX_PROMPT_COMMITS: usize = 200;
+
+/// A public signature removed or changed by the diff, which may break dependents
+struct ApiChange {
+    kind: &'static str,
+    path: String,
+    line: String,
+}
+
+pub struct VersionBumpCommand {
+    pub create_tag: bool,
+}
+
+impl VersionBumpCommand {
+    /// The bump a conventional commit message asks for, if any
+    fn declared_bump(message: &str) -> Option<Bump> {
+        let header = message.lines().next()?;
+        let (prefix, _) = header.split_once(':')?;
+
+        let breaking_footer = message.lines().any(|line| {
+            line.starts_with("BREAKING CHANGE:") || line.starts_with("BREAKING-CHANGE:")
+        });
+        if prefix.ends_with('!') || breaking_footer {
+            return Some(Bump::Major);
+        }
+
+        match prefix.split('(').next()? {
+            "feat" => Some(Bump::Minor),
+            "fix" | "perf" | "revert" => Some(Bump::Patch),
+            _ => None,
+        }
+    }
+
+    fn api_change_kind(path: &str, line: &str) -> Option<&'static str> {
+        let line = line.trim_start();
+
+        if path.ends_with(".rs") && RUST_PUBLIC_ITEMS.iter().any(|item| line.starts_with(item)) {
+            return Some("Rust public item");
+        }
+
+        let is_script = [".ts", ".tsx", ".js", ".jsx", ".mjs", ".cjs"]
+            .iter()
+            .any(|extension| path.ends_with(extension));
+        if is_script && line.starts_with("export ") {
+            return Some("exported symbol");
+        }
+
+        if CLI_FLAG_MARKERS.iter().any(|marker| line.contains(marker)) {
+            return Some("CLI flag");
+        }
+
+        None
+    }
+
+    /// Finds public signatures that were removed, or changed since the old line is gone
+    fn find_api_changes(diff: &str) -> Vec<ApiChange> {
+        let added: HashSet<&str> = diff
+            .lines()
+            .filter(|line| line.starts_with('+') && !line.starts_with("+++"))
+            .map(|line| line[1..].trim())
+            .collect();
+
+        let mut path = String::new();
+        let mut changes = Vec::new();
+        for line in diff.lines() {
+            if let Some(new_path) = line.strip_prefix("+++ b/") {
+                path = new_path.to_string();
+            } else if let Some(old_path) = line.strip_prefix("--- a/") {
+                // Keeps the path of deleted files, whose new side is /dev/null
+                path = old_path.to_string();
+            } else if let Some(removed) = line.strip_prefix('-') {
+                if added.contains(removed.trim()) {
+                    continue;
+                }
+                if let Some(kind) = Self::api_change_kind(&path, removed) {
+                    changes.push(ApiChange {
+                        kind,
+                        path: path.clone(),
+                        line: removed.trim().to_string(),
+                    });
+                }
+            }
+        }
+
+        changes
+    }
+
+    /// The newest commits, with a note of how many older ones were left out
+    fn format_commits(commits: &[(String, String)], format: impl Fn(&str, &str) -> String) -> String {
+        let mut lines = commits
+            .iter()
+            .take(MAX_PROMPT_COMMITS)
+            .map(|(hash, message)| format(hash, message))
+            .collect::<Vec<_>>();
+        if commits.len() > MAX_PROMPT_COMMITS {
+            lines.push(format!(
+                "- ... and {} older commits",
+                commits.len() - MAX_PROMPT_COMMITS
+            ));
+        }
+        lines.join("\n")
+    }
+
+    fn get_ai_prompt(
+        commits: &[(String, String)],
+        declared: Bump,
+        api_changes: &[ApiChange],
+    ) -> AIPrompt {
+        let system_prompt = String::from(indoc! {"
+            You are a release manager that recommends the next semantic version.
+            - major: incompatible changes to the public API, CLI or behavior users rely on
+            - minor: new backwards compatible functionality
+            - patch: backwards compatible fixes
+            Public signatures that were removed or changed without being declared as breaking
+            must be judged from their code: compatible changes (e.g. a new optional field) are fine.
+            The first line of your answer must be `BUMP: major`, `BUMP: minor` or `BUMP: patch`.
+            Follow it with a short markdown justification citing commits by their short hash.
+        "});
+
+        let commits = Self::format_commits(commits, |hash, message| {
+            format!("- {}: {}", hash, message.lines().next().unwrap_or_default())
+        });
+
+        let api_changes = if api_changes.is_empty() {
+            "(none found)".to_string()
+        } else {
+            api_changes
+                .iter()
+                .map(|change| format!("- {} in `{}`: `{}`", change.kind, change.path, change.line))
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+
+        let user_prompt = formatdoc! {"
+            Commits since the last release:
+            {commits}
+
+            The commit messages declare at least a {declared} bump.
+
+            Public signatures removed or changed by the diff:
+            {api_changes}
+            "
+        };
+
+        AIPrompt {
+            system_prompt,
+            user_prompt,
+        }
+    }
+
+    fn parse_bump(response: &str) -> Option<Bump> {
+        let line = response.trim().lines().next()?;
+        match line.strip_prefix("BUMP:")?.trim().to_lowercase().as_str() {
+            "major" => Some(Bump::Major),
+            "minor" => Some(Bump::Minor),
+            "patch" => Some(Bump::Patch),
+            _ => None,
+        }
+    }
+
+    async fn complete(
+        llm: &LLMProvider,
+        params: &GenerationParams,
+        ai_prompt: AIPrompt,
+    ) -> Result<String, GitAIError> {
+        let system_message = Message {
+            role: Role::System,
+            content: ai_prompt.system_prompt,
+        };
+
+        let user_message = Message {
+            role: Role::User,
+            content: ai_prompt.user_prompt,
+        };
+
+        let messages = vec![system_message, user_message];
+
+        Ok(llm.complete(&messages, params).await?)
+    }
+
+    async fn create_tag(
+        llm: &LLMProvider,
+        params: &GenerationParams,
+        tag_name: &str,
+        commits: &[(String, String)],
+    ) -> Result<(), GitAIError> {
+        let mut spinner = Spinner::new(
+            spinners::Dots,
+            "Generating tag message...".to_string(),
+            Color::Green,
+        );
+
+        let ai_prompt = AIPrompt {
+            system_prompt: String::from(indoc! {"
+                You are a release manager that writes annotated git tag messages.
+                Output only the tag message: a one line title, a blank line, then the notable
+                changes as `- ` bullet points grouped by features, fixes and breaking changes.
+            "}),
+            user_prompt: format!(
+                "Write the tag message for {} from these commits:\n{}",
+                tag_name,
+                Self::format_commits(commits, |_, message| {
+                    format!("- {}", message.replace('\n', "\n  "))
+                })
+            ),
+        };
+
+        let response = Self::complete(llm, params, ai_prompt).await;
+
+        spinner.stop();
+
+        let tag_message = response?.trim().to_string();
+        print_markdown(format!("```\n{}\n```", tag_message))?;
+
+        let create = Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("Create annotated tag '{}'?", tag_name))
+            .default(false)
+            .interact_on(&Term::stderr())?;
+
+        if create {
+            Tag::create_annotated(tag_name, &tag_message)?;
+            println!("Created tag '{}'", tag_name);
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Command for VersionBumpCommand {
+    async fn execute(&self, llm: LLMProvider, params: GenerationParams) -> Result<(), GitAIError> {
+        let latest = Tag::latest_semver()?;
+
+        let (commits, api_changes) = match &latest {
+            Some(tag) => {
+                let commits = Commit::list_messages(&format!("{}..HEAD", tag.name))?;
+                let api_changes = match Diff::from_commits_range(&tag.name, "HEAD", false) {
+                    Ok(Diff::CommitsRange { diff, .. }) => Self::find_api_changes(&diff),
+                    _ => Vec::new(),
+                };
+                (commits, api_changes)
+            }
+            // Everything is new in a first release, there is no API to break yet
+            None => (Commit::list_messages("HEAD")?, Vec::new()),
+        };
+
+        if commits.is_empty() {
+            println!("No commits since the last release, nothing to bump");
+            return Ok(());
+        }
+
+        let declared = commits
+            .iter()
+            .filter_map(|(_, message)| Self::declared_bump(message))
+            .max()
+            .unwrap_or(Bump::Patch);
+
+        let mut spinner = Spinner::new(
+            spinners::Dots,
+            "Analyzing changes...".to_string(),
+            Color::Green,
+        );
+
+        let response = Self::complete(
+            &llm,
+            &params,
+            Self::get_ai_prompt(&commits, declared, &api_changes),
+        )
+        .await;
+
+        spinner.stop();
+
+        let (bump, justification) = match response {
+            Ok(response) => {
+                let bump = Self::parse_bump(&response).map_or(declared, |bump| bump.max(declared));
+                let justification = response
+                    .trim()
+                    .lines()
+                    .skip_while(|line| line.starts_with("BUMP:"))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                (bump, justification)
+            }
+            Err(GitAIError::LLMError(LLMError::DryRun)) => return Err(LLMError::DryRun.into()),
+            Err(e) => {
+                println!("Error: {}", e);
+                (
+                    declared,
+                    "Based on the conventional commit types only.".to_string(),
+                )
+            }
+        };
+
+        let current = latest
+            .as_ref()
+            .map(|tag| tag.version.clone())
+            .unwrap_or(Version::parse("0.0.0").unwrap());
+
+        // Before 1.0.0 breaking changes only bump the minor version
+        let applied = if bump == Bump::Major && current.major == 0 {
+            Bump::Minor
+        } else {
+            bump
+        };
+        let next = current.bump(applied);
+
+        let prefix = match &latest {
+            Some(tag) if !tag.name.starts_with('v') => "",
+            _ => "v",
+        };
+        let tag_name = format!("{}{}", prefix, next);
+
+        let api_section = if api_changes.is_empty() {
+            String::new()
+        } else {
+            format!(
+                "\n## Public API changes\n{}\n",
+                api_changes
+                    .iter()
+                    .map(|change| format!(
+                        "* {} in `{}`: `{}`",
+                        change.kind, change.path, change.line
+                    ))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            )
+        };
+
+        print_markdown(formatdoc! {"
+            # Version bump: {applied}
+            `{current}` -> `{next}`
+            {api_section}
+            {justification}
+            ",
+            current = latest.as_ref().map_or("none".to_string(), |tag| tag.name.clone()),
+            next = tag_name,
+        })?;
+
+        if self.create_tag {
+            Self::create_tag(&llm, &params, &tag_name, &commits).await?;
+        }
+
+        Ok(())
+    }
+}
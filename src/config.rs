@@ -1,12 +1,16 @@
 use crate::cli::LLMProviderType;
 use crate::error::GitAIError;
+use crate::llm::GenerationParams;
 use crate::Cli;
+use clap::CommandFactory;
 use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::{self, BufRead};
 use std::path::PathBuf;
+use std::str::FromStr;
 
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize)]
 pub struct GitAIConfig {
     #[serde(
         default = "default_provider",
@@ -17,12 +21,170 @@ pub struct GitAIConfig {
     pub model: Option<String>,
 
     pub api_key: Option<String>,
+
+    #[serde(skip)]
+    pub api_keys: HashMap<LLMProviderType, String>,
+
+    /// Provider and model pairs tried in order when the primary provider fails
+    #[serde(skip)]
+    pub fallback: Vec<(LLMProviderType, Option<String>)>,
+
+    pub branch_pattern: Option<String>,
+
+    pub commit_convention: Option<String>,
+
+    pub ollama_host: Option<String>,
+
+    pub ollama_port: Option<u16>,
+
+    pub base_url: Option<String>,
+
+    #[serde(skip)]
+    pub headers: Vec<(String, String)>,
+
+    pub auth_scheme: Option<String>,
+
+    pub azure_endpoint: Option<String>,
+
+    pub azure_deployment: Option<String>,
+
+    pub azure_api_version: Option<String>,
+
+    pub azure_token_command: Option<String>,
+
+    /// The shell command the exec provider runs for every request
+    pub exec_command: Option<String>,
+
+    pub connect_timeout: Option<u64>,
+
+    pub read_timeout: Option<u64>,
+
+    pub max_attempts: Option<u32>,
+
+    /// Proxy for every request, instead of the `HTTP(S)_PROXY` environment variables
+    pub proxy: Option<String>,
+
+    /// Comma separated hosts reached without the proxy
+    pub no_proxy: Option<String>,
+
+    /// PEM files of root certificates trusted on top of the system ones
+    #[serde(skip)]
+    pub ca_certs: Vec<PathBuf>,
+
+    /// PEM certificate and PKCS#8 key presented to servers that require mutual TLS
+    pub client_cert: Option<PathBuf>,
+
+    pub client_key: Option<PathBuf>,
+
+    pub cache_ttl_days: Option<u64>,
+
+    pub cache_max_size_mb: Option<u64>,
+
+    /// The fixture the replay provider answers from
+    pub replay_file: Option<PathBuf>,
+
+    /// The fixture exchanges are recorded to
+    #[serde(skip)]
+    pub record_file: Option<PathBuf>,
+
+    /// Generation parameters of every command
+    #[serde(skip)]
+    pub generation: GenerationParams,
+
+    /// Generation parameters of a single command, by command name, e.g. `lint-message`
+    #[serde(skip)]
+    pub command_generation: HashMap<String, GenerationParams>,
+
+    #[serde(skip)]
+    pub no_cache: bool,
+
+    #[serde(skip)]
+    pub dry_run: bool,
 }
 
 fn default_provider() -> LLMProviderType {
     LLMProviderType::Phind
 }
 
+/// Parses a `Name: value` header
+fn parse_header(header: &str) -> Result<(String, String), GitAIError> {
+    let (name, value) = header.split_once(':').ok_or_else(|| {
+        GitAIError::ConfigError(format!("Invalid header '{}', expected 'Name: value'", header))
+    })?;
+    Ok((name.trim().to_string(), value.trim().to_string()))
+}
+
+/// Parses a `provider:model` list, e.g. `openai:gpt-4o,ollama:llama3.2`; the model is optional
+fn parse_fallback(value: &str) -> Result<Vec<(LLMProviderType, Option<String>)>, GitAIError> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (provider, model) = match entry.split_once(':') {
+                Some((provider, model)) => (provider, Some(model.to_string())),
+                None => (entry, None),
+            };
+            let provider = provider.parse().map_err(|_| {
+                GitAIError::ConfigError(format!("Invalid provider '{}' in FALLBACK", provider))
+            })?;
+            Ok((provider, model))
+        })
+        .collect()
+}
+
+fn parse_number<T: FromStr>(key: &str, value: &str) -> Result<T, GitAIError> {
+    value
+        .parse()
+        .map_err(|_| GitAIError::ConfigError(format!("Invalid {} '{}'", key, value)))
+}
+
+/// Generation parameter keys, also valid behind a command prefix, e.g. `GENERATE_TEMPERATURE`
+const GENERATION_KEYS: [&str; 5] = ["TEMPERATURE", "TOP_P", "MAX_TOKENS", "STOP", "SEED"];
+
+/// Sets the generation parameter `name` of `params`; `STOP` is a comma separated list
+fn parse_generation_param(
+    params: &mut GenerationParams,
+    name: &str,
+    key: &str,
+    value: &str,
+) -> Result<(), GitAIError> {
+    match name {
+        "TEMPERATURE" => params.temperature = Some(parse_number(key, value)?),
+        "TOP_P" => params.top_p = Some(parse_number(key, value)?),
+        "MAX_TOKENS" => params.max_tokens = Some(parse_number(key, value)?),
+        "STOP" => {
+            params.stop = value
+                .split(',')
+                .filter(|stop| !stop.is_empty())
+                .map(String::from)
+                .collect()
+        }
+        "SEED" => params.seed = Some(parse_number(key, value)?),
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Splits `LINT_MESSAGE_TEMPERATURE` into the command `lint-message` and `TEMPERATURE`.
+/// A misspelled command would silently keep the defaults, so it is an error
+fn split_command_key(key: &str) -> Result<Option<(String, &'static str)>, GitAIError> {
+    let Some((command, name)) = GENERATION_KEYS.iter().find_map(|name| {
+        let command = key.strip_suffix(name)?.strip_suffix('_')?;
+        (!command.is_empty()).then(|| (command.to_lowercase().replace('_', "-"), *name))
+    }) else {
+        return Ok(None);
+    };
+
+    if Cli::command().find_subcommand(&command).is_none() {
+        return Err(GitAIError::ConfigError(format!(
+            "Unknown command '{}' in {}",
+            command, key
+        )));
+    }
+    Ok(Some((command, name)))
+}
+
 fn deserialize_llm_provider<'de, D>(deserializer: D) -> Result<LLMProviderType, D::Error>
 where
     D: Deserializer<'de>,
@@ -33,24 +195,60 @@ where
 
 impl GitAIConfig {
     pub fn build(cli: &Cli) -> Result<Self, GitAIError> {
-        let default = GitAIConfig::from_file()?;
+        Self::with_cli(cli, GitAIConfig::from_file()?)
+    }
 
+    /// The options given on the command line take precedence over those of the config file
+    fn with_cli(cli: &Cli, default: GitAIConfig) -> Result<Self, GitAIError> {
         let provider = cli.provider.as_ref().cloned().unwrap_or(default.provider);
         let model = cli.model.clone().or(default.model);
-        let api_key = cli.api_key.clone().or(default.api_key);
+        // The key of the chosen provider, not of the one configured in the file
+        let api_key = cli.api_key.clone().or(default.api_keys.get(&provider).cloned());
+        let base_url = cli.base_url.clone().or(default.base_url);
+
+        // --replay stands for the replay provider and its fixture
+        let provider = match cli.replay {
+            Some(_) => LLMProviderType::Replay,
+            None => provider,
+        };
+        let replay_file = cli.replay.clone().or(default.replay_file);
+
+        // So does --exec for the exec provider and its command
+        let provider = match cli.exec {
+            Some(_) => LLMProviderType::Exec,
+            None => provider,
+        };
+        let exec_command = cli.exec.clone().or(default.exec_command);
+
+        let mut headers = default.headers;
+        for header in &cli.headers {
+            headers.push(parse_header(header)?);
+        }
 
         Ok(GitAIConfig {
             provider,
             model,
             api_key,
+            base_url,
+            headers,
+            replay_file,
+            exec_command,
+            record_file: cli.record.clone(),
+            no_cache: cli.no_cache,
+            dry_run: cli.dry_run,
+            ..default
         })
     }
 
-    fn get_config_path() -> Result<PathBuf, GitAIError> {
+    pub fn get_config_dir() -> Result<PathBuf, GitAIError> {
         let home_dir = dirs::home_dir().ok_or_else(|| {
             GitAIError::ConfigError("Could not determine home directory".to_string())
         })?;
-        Ok(home_dir.join(".gitai").join(".env"))
+        Ok(home_dir.join(".gitai"))
+    }
+
+    fn get_config_path() -> Result<PathBuf, GitAIError> {
+        Ok(Self::get_config_dir()?.join(".env"))
     }
 
     fn from_file() -> Result<Self, GitAIError> {
@@ -67,7 +265,33 @@ impl GitAIConfig {
 
         let mut provider = default_provider();
         let mut model = None;
-        let mut api_key = None;
+        let mut api_keys = HashMap::new();
+        let mut fallback = Vec::new();
+        let mut branch_pattern = None;
+        let mut commit_convention = None;
+        let mut ollama_host = None;
+        let mut ollama_port = None;
+        let mut base_url = None;
+        let mut headers = Vec::new();
+        let mut auth_scheme = None;
+        let mut azure_endpoint = None;
+        let mut azure_deployment = None;
+        let mut azure_api_version = None;
+        let mut azure_token_command = None;
+        let mut exec_command = None;
+        let mut connect_timeout = None;
+        let mut read_timeout = None;
+        let mut max_attempts = None;
+        let mut proxy = None;
+        let mut no_proxy = None;
+        let mut ca_certs = Vec::new();
+        let mut client_cert = None;
+        let mut client_key = None;
+        let mut cache_ttl_days = None;
+        let mut cache_max_size_mb = None;
+        let mut replay_file = None;
+        let mut generation = GenerationParams::default();
+        let mut command_generation: HashMap<String, GenerationParams> = HashMap::new();
 
         // Parse the .env file
         let reader = io::BufReader::new(file);
@@ -81,40 +305,153 @@ impl GitAIConfig {
                     "PROVIDER" => {
                         provider = value.parse().unwrap_or(default_provider());
                     },
-                    "MODEL" => {
-                        if !value.is_empty() {
-                            model = Some(value.to_string());
-                        }
+                    "MODEL" if !value.is_empty() => {
+                        model = Some(value.to_string());
                     },
-                    "OPENAI_API_KEY" if provider == LLMProviderType::Openai => {
-                        if !value.is_empty() {
-                            api_key = Some(value.to_string());
-                        }
+                    "OPENAI_API_KEY" if !value.is_empty() => {
+                        api_keys.insert(LLMProviderType::Openai, value.to_string());
                     },
-                    "PHIND_API_KEY" if provider == LLMProviderType::Phind => {
-                        if !value.is_empty() {
-                            api_key = Some(value.to_string());
-                        }
+                    "OPENAI_COMPATIBLE_API_KEY" if !value.is_empty() => {
+                        api_keys.insert(LLMProviderType::OpenaiCompatible, value.to_string());
                     },
-                    "ANTHROPIC_API_KEY" if provider == LLMProviderType::Anthropic => {
-                        if !value.is_empty() {
-                            api_key = Some(value.to_string());
-                        }
+                    "PHIND_API_KEY" if !value.is_empty() => {
+                        api_keys.insert(LLMProviderType::Phind, value.to_string());
                     },
-                    "GROQ_API_KEY" if provider == LLMProviderType::Grok => {
-                        if !value.is_empty() {
-                            api_key = Some(value.to_string());
-                        }
+                    "ANTHROPIC_API_KEY" if !value.is_empty() => {
+                        api_keys.insert(LLMProviderType::Anthropic, value.to_string());
+                    },
+                    "GEMINI_API_KEY" if !value.is_empty() => {
+                        api_keys.insert(LLMProviderType::Gemini, value.to_string());
+                    },
+                    "AZURE_OPENAI_API_KEY" if !value.is_empty() => {
+                        api_keys.insert(LLMProviderType::Azure, value.to_string());
+                    },
+                    "GROQ_API_KEY" if !value.is_empty() => {
+                        api_keys.insert(LLMProviderType::Grok, value.to_string());
+                    },
+                    "FALLBACK" if !value.is_empty() => {
+                        fallback = parse_fallback(value)?;
+                    },
+                    "BRANCH_PATTERN" if !value.is_empty() => {
+                        branch_pattern = Some(value.to_string());
+                    },
+                    "COMMIT_CONVENTION" if !value.is_empty() => {
+                        commit_convention = Some(value.to_string());
+                    },
+                    "BASE_URL" if !value.is_empty() => {
+                        base_url = Some(value.to_string());
+                    },
+                    "HEADER" if !value.is_empty() => {
+                        headers.push(parse_header(value)?);
+                    },
+                    "AUTH_SCHEME" if !value.is_empty() => {
+                        auth_scheme = Some(value.to_string());
+                    },
+                    "AZURE_ENDPOINT" if !value.is_empty() => {
+                        azure_endpoint = Some(value.to_string());
+                    },
+                    "AZURE_DEPLOYMENT" if !value.is_empty() => {
+                        azure_deployment = Some(value.to_string());
+                    },
+                    "AZURE_API_VERSION" if !value.is_empty() => {
+                        azure_api_version = Some(value.to_string());
+                    },
+                    "AZURE_TOKEN_COMMAND" if !value.is_empty() => {
+                        azure_token_command = Some(value.to_string());
+                    },
+                    "EXEC_COMMAND" if !value.is_empty() => {
+                        exec_command = Some(value.to_string());
+                    },
+                    "CONNECT_TIMEOUT" if !value.is_empty() => {
+                        connect_timeout = Some(parse_number("CONNECT_TIMEOUT", value)?);
                     },
-                    _ => {}
+                    "READ_TIMEOUT" if !value.is_empty() => {
+                        read_timeout = Some(parse_number("READ_TIMEOUT", value)?);
+                    },
+                    "MAX_ATTEMPTS" if !value.is_empty() => {
+                        max_attempts = Some(parse_number("MAX_ATTEMPTS", value)?);
+                    },
+                    "PROXY" if !value.is_empty() => {
+                        proxy = Some(value.to_string());
+                    },
+                    "NO_PROXY" if !value.is_empty() => {
+                        no_proxy = Some(value.to_string());
+                    },
+                    "CA_CERT" if !value.is_empty() => {
+                        ca_certs.push(PathBuf::from(value));
+                    },
+                    "CLIENT_CERT" if !value.is_empty() => {
+                        client_cert = Some(PathBuf::from(value));
+                    },
+                    "CLIENT_KEY" if !value.is_empty() => {
+                        client_key = Some(PathBuf::from(value));
+                    },
+                    "CACHE_TTL_DAYS" if !value.is_empty() => {
+                        cache_ttl_days = Some(parse_number("CACHE_TTL_DAYS", value)?);
+                    },
+                    "CACHE_MAX_SIZE_MB" if !value.is_empty() => {
+                        cache_max_size_mb = Some(parse_number("CACHE_MAX_SIZE_MB", value)?);
+                    },
+                    "REPLAY_FILE" if !value.is_empty() => {
+                        replay_file = Some(PathBuf::from(value));
+                    },
+                    "OLLAMA_HOST" if !value.is_empty() => {
+                        ollama_host = Some(value.to_string());
+                    },
+                    "OLLAMA_PORT" if !value.is_empty() => {
+                        ollama_port = Some(parse_number("OLLAMA_PORT", value)?);
+                    },
+                    _ if value.is_empty() => {}
+                    _ if GENERATION_KEYS.contains(&key) => {
+                        parse_generation_param(&mut generation, key, key, value)?;
+                    },
+                    _ => {
+                        if let Some((command, name)) = split_command_key(key)? {
+                            let params = command_generation.entry(command).or_default();
+                            parse_generation_param(params, name, key, value)?;
+                        }
+                    }
                 }
             }
         }
 
+        // Keys are kept for every provider, fallbacks may need them
+        let api_key = api_keys.get(&provider).cloned();
+
         Ok(GitAIConfig {
             provider,
             model,
             api_key,
+            api_keys,
+            fallback,
+            branch_pattern,
+            commit_convention,
+            ollama_host,
+            ollama_port,
+            base_url,
+            headers,
+            auth_scheme,
+            azure_endpoint,
+            azure_deployment,
+            azure_api_version,
+            azure_token_command,
+            exec_command,
+            connect_timeout,
+            read_timeout,
+            max_attempts,
+            proxy,
+            no_proxy,
+            ca_certs,
+            client_cert,
+            client_key,
+            cache_ttl_days,
+            cache_max_size_mb,
+            replay_file,
+            record_file: None,
+            generation,
+            command_generation,
+            no_cache: false,
+            dry_run: false,
         })
     }
 }
@@ -125,6 +462,82 @@ impl Default for GitAIConfig {
             provider: default_provider(),
             model: None,
             api_key: None,
+            api_keys: HashMap::new(),
+            fallback: Vec::new(),
+            branch_pattern: None,
+            commit_convention: None,
+            ollama_host: None,
+            ollama_port: None,
+            base_url: None,
+            headers: Vec::new(),
+            auth_scheme: None,
+            azure_endpoint: None,
+            azure_deployment: None,
+            azure_api_version: None,
+            azure_token_command: None,
+            exec_command: None,
+            connect_timeout: None,
+            read_timeout: None,
+            max_attempts: None,
+            proxy: None,
+            no_proxy: None,
+            ca_certs: Vec::new(),
+            client_cert: None,
+            client_key: None,
+            cache_ttl_days: None,
+            cache_max_size_mb: None,
+            replay_file: None,
+            record_file: None,
+            generation: GenerationParams::default(),
+            command_generation: HashMap::new(),
+            no_cache: false,
+            dry_run: false,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn splits_a_command_key() {
+        let (command, name) = split_command_key("LINT_MESSAGE_TEMPERATURE").unwrap().unwrap();
+
+        assert_eq!(command, "lint-message");
+        assert_eq!(name, "TEMPERATURE");
+        assert!(split_command_key("TEMPERATURE").unwrap().is_none());
+        assert!(split_command_key("BRANCH_PATTERN").unwrap().is_none());
+    }
+
+    #[test]
+    fn uses_the_api_key_of_the_provider_given_on_the_command_line() {
+        let default = GitAIConfig {
+            provider: LLMProviderType::Openai,
+            api_key: Some("sk-openai".to_string()),
+            api_keys: HashMap::from([
+                (LLMProviderType::Openai, "sk-openai".to_string()),
+                (LLMProviderType::Anthropic, "sk-ant".to_string()),
+            ]),
+            ..Default::default()
+        };
+        let config = |args: &[&str]| {
+            let cli = Cli::parse_from(["gitai"].iter().chain(args).chain(&["generate"]));
+            GitAIConfig::with_cli(&cli, default.clone()).unwrap()
+        };
+
+        assert_eq!(config(&[]).api_key.unwrap(), "sk-openai");
+        assert_eq!(config(&["-p", "anthropic"]).api_key.unwrap(), "sk-ant");
+        assert_eq!(config(&["-p", "gemini"]).api_key, None);
+        assert_eq!(config(&["-p", "gemini", "-a", "key"]).api_key.unwrap(), "key");
+    }
+
+    #[test]
+    fn rejects_a_key_for_an_unknown_command() {
+        assert!(matches!(
+            split_command_key("GENRATE_TEMPERATURE"),
+            Err(GitAIError::ConfigError(_))
+        ));
+    }
+}
@@ -1,5 +1,8 @@
 use crate::{
-    git_entity::{commit::CommitError, diff::DiffError},
+    git_entity::{
+        branch::BranchError, commit::CommitError, conflict::ConflictError, diff::DiffError,
+        git_command::GitCommandError, hook::HookError, rebase::RebaseError, tag::TagError,
+    },
     llm::LLMError,
 };
 use std::io;
@@ -13,6 +16,24 @@ pub enum GitAIError {
     #[error("{0}")]
     GitDiffError(#[from] DiffError),
 
+    #[error("{0}")]
+    GitBranchError(#[from] BranchError),
+
+    #[error("{0}")]
+    GitRebaseError(#[from] RebaseError),
+
+    #[error("{0}")]
+    GitConflictError(#[from] ConflictError),
+
+    #[error("{0}")]
+    GitCommandError(#[from] GitCommandError),
+
+    #[error("{0}")]
+    GitHookError(#[from] HookError),
+
+    #[error("{0}")]
+    GitTagError(#[from] TagError),
+
     #[error("Missing API key for {0}, use --api-key or GITAI_API_KEY env variable")]
     MissingApiKey(String),
 
@@ -21,6 +42,9 @@ pub enum GitAIError {
     #[error("Invalid arguments: {0}")]
     InvalidArguments(String),
 
+    #[error("Commit message check failed:\n{0}")]
+    InvalidCommitMessage(String),
+
     // #[error("Invalid configuration: {0}")]
     // InvalidConfiguration(String),
     #[error("Configuration error: {0}")]
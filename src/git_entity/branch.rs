@@ -0,0 +1,72 @@
+use crate::error::GitAIError;
+use std::process::Command;
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone)]
+pub enum BranchError {
+    #[error("'{0}' is not a valid branch name")]
+    InvalidName(String),
+
+    #[error("Branch '{0}' already exists")]
+    AlreadyExists(String),
+
+    #[error("Could not create branch '{0}': {1}")]
+    CreateFailed(String, String),
+}
+
+#[derive(Clone, Debug)]
+pub struct Branch {
+    pub name: String,
+}
+
+impl Branch {
+    pub fn new(name: String) -> Result<Self, GitAIError> {
+        Self::is_valid_name(&name)?;
+
+        Ok(Branch { name })
+    }
+
+    pub fn is_valid_name(name: &str) -> Result<(), GitAIError> {
+        let output = Command::new("git")
+            .args(["check-ref-format", "--branch", name])
+            .output()?;
+
+        if output.status.success() {
+            return Ok(());
+        }
+
+        Err(BranchError::InvalidName(name.to_string()).into())
+    }
+
+    pub fn exists(&self) -> Result<bool, GitAIError> {
+        let output = Command::new("git")
+            .args([
+                "show-ref",
+                "--verify",
+                "--quiet",
+                &format!("refs/heads/{}", self.name),
+            ])
+            .output()?;
+
+        Ok(output.status.success())
+    }
+
+    pub fn create_and_switch(&self) -> Result<(), GitAIError> {
+        if self.exists()? {
+            return Err(BranchError::AlreadyExists(self.name.clone()).into());
+        }
+
+        let output = Command::new("git")
+            .args(["switch", "-c", &self.name])
+            .output()?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8(output.stderr)?;
+            return Err(
+                BranchError::CreateFailed(self.name.clone(), stderr.trim().to_string()).into(),
+            );
+        }
+
+        Ok(())
+    }
+}
@@ -46,7 +46,83 @@ impl Commit {
         Err(CommitError::InvalidCommit(sha.to_string()).into())
     }
 
-    fn get_full_hash(sha: &str) -> Result<String, GitAIError> {
+    pub fn list_range(from: &str, to: &str) -> Result<Vec<String>, GitAIError> {
+        let output = Command::new("git")
+            .args(["rev-list", "--reverse", &format!("{}..{}", from, to)])
+            .output()?;
+
+        let output_str = String::from_utf8(output.stdout)?;
+        Ok(output_str.lines().map(String::from).collect())
+    }
+
+    /// The short hash and message of the non-merge commits in `range` (`<from>..<to>`, or a
+    /// commit for all of its history), newest first, read in one call without their diffs
+    pub fn list_messages(range: &str) -> Result<Vec<(String, String)>, GitAIError> {
+        let output = Command::new("git")
+            .args(["log", "--no-merges", "--format=%h%x1f%B%x1e", range])
+            .output()?;
+
+        let output_str = String::from_utf8(output.stdout)?;
+        Ok(output_str
+            .split('\x1e')
+            .filter_map(|record| {
+                let (hash, message) = record.trim().split_once('\x1f')?;
+                Some((hash.to_string(), message.trim().to_string()))
+            })
+            .collect())
+    }
+
+    /// The latest non-merge commits in `range` that touched `path`, relative to the repository
+    /// root, newest first
+    pub fn list_touching(range: &str, path: &str, limit: usize) -> Result<Vec<String>, GitAIError> {
+        let output = Command::new("git")
+            .args([
+                "log",
+                "--no-merges",
+                "--format=%H",
+                "-n",
+                &limit.to_string(),
+                range,
+                "--",
+                &format!(":(top){}", path),
+            ])
+            .output()?;
+
+        let output_str = String::from_utf8(output.stdout)?;
+        Ok(output_str.lines().map(String::from).collect())
+    }
+
+    /// The best common ancestor of `a` and `b`, where a branch forked from the other
+    pub fn merge_base(a: &str, b: &str) -> Result<String, GitAIError> {
+        let output = Command::new("git").args(["merge-base", a, b]).output()?;
+
+        let output_str = String::from_utf8(output.stdout)?;
+        match output_str.trim() {
+            "" => Err(CommitError::InvalidCommit(format!("{}...{}", a, b)).into()),
+            base => Ok(base.to_string()),
+        }
+    }
+
+    pub fn is_merge(sha: &str) -> Result<bool, GitAIError> {
+        let output = Command::new("git")
+            .args(["rev-list", "--parents", "-n", "1", sha])
+            .output()?;
+
+        let output_str = String::from_utf8(output.stdout)?;
+        Ok(output_str.split_whitespace().count() > 2)
+    }
+
+    /// Whether the commit is reachable from any remote-tracking branch
+    pub fn is_pushed(sha: &str) -> Result<bool, GitAIError> {
+        let output = Command::new("git")
+            .args(["branch", "-r", "--contains", sha])
+            .output()?;
+
+        let output_str = String::from_utf8(output.stdout)?;
+        Ok(!output_str.trim().is_empty())
+    }
+
+    pub fn get_full_hash(sha: &str) -> Result<String, GitAIError> {
         let output = Command::new("git").args(["rev-parse", sha]).output()?;
 
         let mut full_hash = String::from_utf8(output.stdout)?;
@@ -58,6 +134,7 @@ impl Commit {
         let output = Command::new("git")
             .args([
                 "diff-tree",
+                "--root",
                 "-p",
                 "--binary",
                 "--no-color",
@@ -74,7 +151,7 @@ impl Commit {
         Ok(diff)
     }
 
-    fn get_message(sha: &str) -> Result<String, GitAIError> {
+    pub fn get_message(sha: &str) -> Result<String, GitAIError> {
         let output = Command::new("git")
             .args(["log", "--format=%B", "-n", "1", sha])
             .output()?;
@@ -0,0 +1,297 @@
+use crate::error::GitAIError;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone)]
+pub enum ConflictError {
+    #[error("No merge, rebase or cherry-pick in progress")]
+    NoOperation,
+
+    #[error("No conflicted files found")]
+    NoConflicts,
+
+    #[error("Could not stage '{0}': {1}")]
+    StageFailed(String, String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operation {
+    Merge,
+    Rebase,
+    CherryPick,
+}
+
+impl Operation {
+    pub fn detect() -> Result<Self, GitAIError> {
+        if Self::git_path_exists("MERGE_HEAD")? {
+            Ok(Operation::Merge)
+        } else if Self::git_path_exists("rebase-merge")? || Self::git_path_exists("rebase-apply")? {
+            Ok(Operation::Rebase)
+        } else if Self::git_path_exists("CHERRY_PICK_HEAD")? {
+            Ok(Operation::CherryPick)
+        } else {
+            Err(ConflictError::NoOperation.into())
+        }
+    }
+
+    /// The ref holding the commit being brought in, "theirs" from git's point of view
+    pub fn incoming_ref(&self) -> &'static str {
+        match self {
+            Operation::Merge => "MERGE_HEAD",
+            Operation::Rebase => "REBASE_HEAD",
+            Operation::CherryPick => "CHERRY_PICK_HEAD",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Operation::Merge => "merge",
+            Operation::Rebase => "rebase",
+            Operation::CherryPick => "cherry-pick",
+        }
+    }
+
+    fn git_path_exists(name: &str) -> Result<bool, GitAIError> {
+        let output = Command::new("git")
+            .args(["rev-parse", "--git-path", name])
+            .output()?;
+
+        let path = String::from_utf8(output.stdout)?;
+        Ok(output.status.success() && Path::new(path.trim()).exists())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ConflictHunk {
+    pub ours: String,
+    pub base: Option<String>,
+    pub theirs: String,
+    start_line: usize,
+    end_line: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct ConflictedFile {
+    /// Relative to the repository root, as git reports it
+    pub path: String,
+    pub hunks: Vec<ConflictHunk>,
+    root: PathBuf,
+}
+
+impl ConflictedFile {
+    /// The conflicted files, and the paths of those that are not UTF-8 text, such as binaries,
+    /// which are left to the user
+    pub fn list() -> Result<(Vec<Self>, Vec<String>), GitAIError> {
+        let root = Self::get_root()?;
+        let output = Command::new("git")
+            .args(["diff", "--name-only", "--diff-filter=U"])
+            .output()?;
+
+        let output_str = String::from_utf8(output.stdout)?;
+        let mut files = Vec::new();
+        let mut skipped = Vec::new();
+        for path in output_str.lines() {
+            match Self::new(&root, path.to_string())? {
+                Some(file) => files.push(file),
+                None => skipped.push(path.to_string()),
+            }
+        }
+
+        if files.is_empty() && skipped.is_empty() {
+            return Err(ConflictError::NoConflicts.into());
+        }
+
+        Ok((files, skipped))
+    }
+
+    /// The paths git reports are relative to it, not to the directory gitai runs in
+    fn get_root() -> Result<PathBuf, GitAIError> {
+        let output = Command::new("git")
+            .args(["rev-parse", "--show-toplevel"])
+            .output()?;
+
+        let root = String::from_utf8(output.stdout)?;
+        Ok(PathBuf::from(root.trim()))
+    }
+
+    /// `None` when the file is not UTF-8 text
+    fn new(root: &Path, path: String) -> Result<Option<Self>, GitAIError> {
+        let Ok(content) = String::from_utf8(fs::read(root.join(&path))?) else {
+            return Ok(None);
+        };
+        let mut hunks = parse_hunks(&content);
+
+        // The working tree only carries the base side with `merge.conflictStyle=diff3`,
+        // so rebuild it from the index stages when it is missing.
+        if hunks.iter().any(|hunk| hunk.base.is_none()) {
+            if let Some(stage_hunks) = Self::hunks_from_stages(&path)? {
+                if stage_hunks.len() == hunks.len() {
+                    for (hunk, stage_hunk) in hunks.iter_mut().zip(stage_hunks) {
+                        hunk.base = hunk.base.take().or(stage_hunk.base);
+                    }
+                }
+            }
+        }
+
+        Ok(Some(ConflictedFile {
+            path,
+            hunks,
+            root: root.to_path_buf(),
+        }))
+    }
+
+    fn get_stage(stage: u8, path: &str) -> Result<Option<String>, GitAIError> {
+        let output = Command::new("git")
+            .args(["show", &format!(":{}:{}", stage, path)])
+            .output()?;
+
+        if !output.status.success() {
+            return Ok(None);
+        }
+
+        Ok(Some(String::from_utf8(output.stdout)?))
+    }
+
+    fn hunks_from_stages(path: &str) -> Result<Option<Vec<ConflictHunk>>, GitAIError> {
+        let (Some(ours), Some(theirs)) = (Self::get_stage(2, path)?, Self::get_stage(3, path)?)
+        else {
+            return Ok(None);
+        };
+        let base = Self::get_stage(1, path)?.unwrap_or_default();
+
+        let work_dir = std::env::temp_dir().join(format!("gitai-conflict-{}", std::process::id()));
+        fs::create_dir_all(&work_dir)?;
+
+        let stage_paths = [
+            work_dir.join("ours"),
+            work_dir.join("base"),
+            work_dir.join("theirs"),
+        ];
+        for (stage_path, content) in stage_paths.iter().zip([&ours, &base, &theirs]) {
+            fs::write(stage_path, content)?;
+        }
+
+        // Exits with the number of conflicts, so the status is not an error indicator
+        let output = Command::new("git")
+            .arg("merge-file")
+            .args(["-p", "--diff3"])
+            .args(&stage_paths)
+            .output();
+
+        let _ = fs::remove_dir_all(&work_dir);
+
+        let merged = String::from_utf8(output?.stdout)?;
+        Ok(Some(parse_hunks(&merged)))
+    }
+
+    /// Replaces the hunks that have a resolution and writes the file back.
+    /// Returns whether any conflict markers remain.
+    pub fn apply_resolutions(&self, resolutions: &[Option<String>]) -> Result<bool, GitAIError> {
+        let content = fs::read_to_string(self.root.join(&self.path))?;
+        let lines: Vec<&str> = content.split_inclusive('\n').collect();
+
+        let mut resolved = String::new();
+        let mut next_line = 0;
+        for (hunk, resolution) in self.hunks.iter().zip(resolutions) {
+            let Some(resolution) = resolution else {
+                continue;
+            };
+
+            resolved.extend(lines[next_line..hunk.start_line].iter().copied());
+            resolved.push_str(resolution);
+            if !resolution.is_empty() && !resolution.ends_with('\n') {
+                resolved.push('\n');
+            }
+            next_line = hunk.end_line + 1;
+        }
+        resolved.extend(lines[next_line..].iter().copied());
+
+        fs::write(self.root.join(&self.path), &resolved)?;
+
+        Ok(!parse_hunks(&resolved).is_empty())
+    }
+
+    pub fn stage(&self) -> Result<(), GitAIError> {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(&self.root)
+            .args(["add", "--", &self.path])
+            .output()?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8(output.stderr)?;
+            return Err(
+                ConflictError::StageFailed(self.path.clone(), stderr.trim().to_string()).into(),
+            );
+        }
+
+        Ok(())
+    }
+}
+
+enum Section {
+    Outside,
+    Ours,
+    Base,
+    Theirs,
+}
+
+fn parse_hunks(content: &str) -> Vec<ConflictHunk> {
+    let mut hunks = Vec::new();
+    let mut section = Section::Outside;
+    let (mut ours, mut base, mut theirs) = (String::new(), None::<String>, String::new());
+    let mut start_line = 0;
+
+    for (index, line) in content.split_inclusive('\n').enumerate() {
+        match section {
+            Section::Outside if line.starts_with("<<<<<<<") => {
+                section = Section::Ours;
+                start_line = index;
+                (ours, base, theirs) = (String::new(), None, String::new());
+            }
+            Section::Outside => {}
+            Section::Ours if line.starts_with("|||||||") => {
+                section = Section::Base;
+                base = Some(String::new());
+            }
+            Section::Ours | Section::Base if line.starts_with("=======") => {
+                section = Section::Theirs;
+            }
+            Section::Ours => ours.push_str(line),
+            Section::Base => base.get_or_insert_with(String::new).push_str(line),
+            Section::Theirs if line.starts_with(">>>>>>>") => {
+                section = Section::Outside;
+                hunks.push(ConflictHunk {
+                    ours: std::mem::take(&mut ours),
+                    base: base.take(),
+                    theirs: std::mem::take(&mut theirs),
+                    start_line,
+                    end_line: index,
+                });
+            }
+            Section::Theirs => theirs.push_str(line),
+        }
+    }
+
+    hunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_a_file_that_is_not_text() {
+        let root = std::env::temp_dir().join(format!("gitai-latin1-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("menu.txt"), b"<<<<<<< HEAD\ncaf\xe9\n=======\nth\xe9\n>>>>>>> main\n")
+            .unwrap();
+
+        let file = ConflictedFile::new(&root, "menu.txt".to_string());
+
+        fs::remove_dir_all(&root).unwrap();
+        assert!(file.unwrap().is_none());
+    }
+}
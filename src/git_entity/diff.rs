@@ -40,9 +40,25 @@ impl Diff {
         Ok(Diff::WorkingTree { staged, diff })
     }
 
+    /// Staged changes compared to `base` instead of HEAD
+    pub fn from_index(base: &str) -> Result<Self, GitAIError> {
+        Commit::is_valid_commit(base)?;
+
+        let output = std::process::Command::new("git")
+            .args(["diff", "--staged", base])
+            .output()?;
+
+        let diff = String::from_utf8(output.stdout)?;
+        if diff.is_empty() {
+            return Err(DiffError::EmptyDiff { staged: true }.into());
+        }
+
+        Ok(Diff::WorkingTree { staged: true, diff })
+    }
+
     pub fn from_commits_range(from: &str, to: &str, triple_dot: bool) -> Result<Self, GitAIError> {
-        let _ = Commit::is_valid_commit(from)?;
-        let _ = Commit::is_valid_commit(to)?;
+        Commit::is_valid_commit(from)?;
+        Commit::is_valid_commit(to)?;
 
         let separator = if triple_dot { "..." } else { ".." };
         let range = format!("{}{}{}", from, separator, to);
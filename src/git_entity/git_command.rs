@@ -0,0 +1,419 @@
+use crate::error::GitAIError;
+use std::fmt;
+use std::process::Command;
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone)]
+pub enum GitCommandError {
+    #[error("Only git commands can be run, got '{0}'")]
+    NotGit(String),
+
+    #[error("Shell syntax is not supported in '{0}'")]
+    ShellSyntax(String),
+
+    #[error("Unterminated quote in '{0}'")]
+    UnterminatedQuote(String),
+}
+
+/// Config overrides that only change how output looks or which identity is recorded,
+/// lowercase as compared; a trailing `*` matches a whole section
+const SAFE_CONFIG: [&str; 11] = [
+    "color.*",
+    "column.*",
+    "advice.*",
+    "user.name",
+    "user.email",
+    "log.date",
+    "log.decorate",
+    "core.quotepath",
+    "diff.renames",
+    "merge.conflictstyle",
+    "init.defaultbranch",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Safety {
+    ReadOnly,
+    LocalMutating,
+    RemoteOrDestructive,
+}
+
+impl fmt::Display for Safety {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Safety::ReadOnly => write!(f, "read-only"),
+            Safety::LocalMutating => write!(f, "local-mutating"),
+            Safety::RemoteOrDestructive => write!(f, "remote/destructive"),
+        }
+    }
+}
+
+pub struct GitCommandOutput {
+    pub success: bool,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// A single `git` invocation proposed by the model, parsed without a shell
+#[derive(Clone, Debug)]
+pub struct GitCommand {
+    pub line: String,
+    args: Vec<String>,
+}
+
+impl GitCommand {
+    pub fn parse(line: &str) -> Result<Self, GitAIError> {
+        let line = line.trim().to_string();
+        let words = split_words(&line)?;
+
+        match words.split_first() {
+            Some((program, args)) if program == "git" => Ok(GitCommand {
+                args: args.to_vec(),
+                line,
+            }),
+            _ => Err(GitCommandError::NotGit(line).into()),
+        }
+    }
+
+    /// Index of the subcommand, skipping global options such as `-C <path>`
+    fn subcommand_index(&self) -> Option<usize> {
+        let mut index = 0;
+        while let Some(arg) = self.args.get(index) {
+            match arg.as_str() {
+                "-C" | "-c" | "--git-dir" | "--work-tree" | "--namespace" => index += 2,
+                arg if arg.starts_with('-') => index += 1,
+                _ => return Some(index),
+            }
+        }
+        None
+    }
+
+    fn subcommand(&self) -> &str {
+        self.subcommand_index()
+            .map(|index| self.args[index].as_str())
+            .unwrap_or_default()
+    }
+
+    /// The arguments with clustered short options also split up, `-fu` gives `-fu -f -u`
+    fn expanded_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        for arg in &self.args {
+            args.push(arg.clone());
+            if let Some(cluster) = arg.strip_prefix('-') {
+                if cluster.len() > 1 && !cluster.starts_with('-') {
+                    args.extend(cluster.chars().map(|flag| format!("-{}", flag)));
+                }
+            }
+        }
+        args
+    }
+
+    fn has_flag(&self, flags: &[&str]) -> bool {
+        self.expanded_args().iter().any(|arg| {
+            flags
+                .iter()
+                .any(|flag| arg == flag || arg.starts_with(&format!("{}=", flag)))
+        })
+    }
+
+    /// The `-c name=value` and `--config-env=name=...` overrides before the subcommand
+    fn config_overrides(&self) -> Vec<(String, String)> {
+        let end = self.subcommand_index().unwrap_or(self.args.len());
+        let mut overrides = Vec::new();
+        let mut index = 0;
+        while index < end {
+            let setting = match self.args[index].as_str() {
+                "-c" | "--config-env" => {
+                    index += 1;
+                    self.args.get(index).map(String::as_str)
+                }
+                arg => arg.strip_prefix("--config-env="),
+            };
+            if let Some((name, value)) = setting.and_then(|setting| setting.split_once('=')) {
+                overrides.push((name.to_lowercase(), value.to_string()));
+            }
+            index += 1;
+        }
+        overrides
+    }
+
+    /// Many settings run programs (`core.sshCommand`, `pager.*`, `diff.external`,
+    /// `credential.helper`, `gpg.program`, any `*.command`...), and aliases hide the real
+    /// subcommand, so only overrides known to be harmless are let through
+    fn has_dangerous_config(&self) -> bool {
+        self.config_overrides().iter().any(|(name, _)| {
+            !SAFE_CONFIG.iter().any(|safe| match safe.strip_suffix('*') {
+                Some(section) => name.starts_with(section),
+                None => name == safe,
+            })
+        })
+    }
+
+    /// Positional arguments after the subcommand
+    fn operands(&self) -> Vec<&str> {
+        let start = self.subcommand_index().map_or(self.args.len(), |index| index + 1);
+
+        self.args[start..]
+            .iter()
+            .filter(|arg| !arg.starts_with('-'))
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// Commands that throw away work or rewrite shared state, and need a typed confirmation
+    pub fn is_destructive(&self) -> bool {
+        if self.has_dangerous_config() {
+            return true;
+        }
+
+        match self.subcommand() {
+            "push" => {
+                self.has_flag(&[
+                    "--force",
+                    "-f",
+                    "--force-with-lease",
+                    "--delete",
+                    "-d",
+                    "--mirror",
+                    "--prune",
+                ]) || self
+                    .operands()
+                    .iter()
+                    .any(|operand| operand.starts_with('+') || operand.starts_with(':'))
+            }
+            "reset" => self.has_flag(&["--hard", "--merge", "--keep"]),
+            "clean" => !self.has_flag(&["-n", "--dry-run"]),
+            // `checkout <tree-ish> <path>` overwrites the path like `checkout -- <path>`
+            "checkout" => {
+                self.has_flag(&["-f", "--force", "-B"])
+                    || self.args.iter().any(|arg| arg == "--" || arg == ".")
+                    || (self.operands().len() > 1 && !self.has_flag(&["-b", "--orphan"]))
+            }
+            "switch" => self.has_flag(&["-f", "--force", "--discard-changes", "-C", "--force-create"]),
+            "restore" => {
+                !self.has_flag(&["--staged", "-S"]) || self.has_flag(&["--worktree", "-W"])
+            }
+            // Force deletes, and renames or copies over an existing branch
+            "branch" => self.has_flag(&["-D", "-M", "-C", "-f", "--force"]),
+            "rm" => self.has_flag(&["-f", "--force"]),
+            "tag" => self.has_flag(&["-f", "--force"]),
+            "worktree" => {
+                self.operands().first() == Some(&"remove") && self.has_flag(&["-f", "--force"])
+            }
+            "stash" => matches!(self.operands().first(), Some(&"drop") | Some(&"clear")),
+            "reflog" => matches!(self.operands().first(), Some(&"expire") | Some(&"delete")),
+            "gc" => self.has_flag(&["--prune"]),
+            "update-ref" => self.has_flag(&["-d"]),
+            "filter-branch" | "filter-repo" | "prune" => true,
+            _ => false,
+        }
+    }
+
+    pub fn classify(&self) -> Safety {
+        if self.is_destructive() {
+            return Safety::RemoteOrDestructive;
+        }
+
+        let operands = self.operands();
+        let read_only = match self.subcommand() {
+            "status" | "log" | "show" | "diff" | "blame" | "shortlog" | "describe"
+            | "rev-parse" | "rev-list" | "ls-files" | "ls-tree" | "cat-file" | "grep"
+            | "show-ref" | "merge-base" | "name-rev" | "check-ref-format" | "whatchanged"
+            | "help" | "version" => true,
+            "branch" => {
+                operands.is_empty()
+                    || self.has_flag(&[
+                        "--list",
+                        "-l",
+                        "-a",
+                        "-r",
+                        "-v",
+                        "-vv",
+                        "--contains",
+                        "--merged",
+                        "--no-merged",
+                        "--show-current",
+                    ])
+            }
+            "tag" => operands.is_empty() || self.has_flag(&["-l", "--list", "-v", "--verify"]),
+            "stash" => matches!(operands.first(), Some(&"list") | Some(&"show")),
+            "remote" => {
+                operands.is_empty() || matches!(operands.first(), Some(&"show") | Some(&"get-url"))
+            }
+            "reflog" => operands.is_empty() || operands.first() == Some(&"show"),
+            "config" => self.has_flag(&["--get", "--get-all", "--list", "-l", "--get-regexp"]),
+            "worktree" => operands.first() == Some(&"list"),
+            _ => false,
+        };
+
+        if read_only {
+            return Safety::ReadOnly;
+        }
+
+        match self.subcommand() {
+            "push" | "pull" | "fetch" | "clone" | "ls-remote" | "request-pull" | "send-email" => {
+                Safety::RemoteOrDestructive
+            }
+            _ => Safety::LocalMutating,
+        }
+    }
+
+    pub fn run(&self) -> Result<GitCommandOutput, GitAIError> {
+        let output = Command::new("git").args(&self.args).output()?;
+
+        Ok(GitCommandOutput {
+            success: output.status.success(),
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        })
+    }
+}
+
+/// Splits a command line into words, honoring quotes and backslash escapes.
+/// Anything that would need a shell to run (pipes, redirects, chaining, substitution)
+/// is rejected so that a command always maps to exactly one git invocation.
+fn split_words(line: &str) -> Result<Vec<String>, GitAIError> {
+    let mut words = Vec::new();
+    let mut current: Option<String> = None;
+    let mut quote: Option<char> = None;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"'), '\\') => {
+                if let Some(next) = chars.next() {
+                    current.get_or_insert_with(String::new).push(next);
+                }
+            }
+            (Some('"'), '$' | '`') => return Err(GitCommandError::ShellSyntax(line.into()).into()),
+            (Some(_), c) => current.get_or_insert_with(String::new).push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                current.get_or_insert_with(String::new);
+            }
+            (None, '\\') => {
+                if let Some(next) = chars.next() {
+                    current.get_or_insert_with(String::new).push(next);
+                }
+            }
+            (None, '|' | '&' | ';' | '<' | '>' | '$' | '`' | '(' | ')' | '~')
+                if c != '~' || current.is_none() =>
+            {
+                return Err(GitCommandError::ShellSyntax(line.into()).into());
+            }
+            (None, c) if c.is_whitespace() => {
+                if let Some(word) = current.take() {
+                    words.push(word);
+                }
+            }
+            (None, c) => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    if quote.is_some() {
+        return Err(GitCommandError::UnterminatedQuote(line.into()).into());
+    }
+    if let Some(word) = current.take() {
+        words.push(word);
+    }
+
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn safety(line: &str) -> Safety {
+        GitCommand::parse(line).unwrap().classify()
+    }
+
+    #[test]
+    fn clustered_short_flags_are_destructive() {
+        assert_eq!(safety("git push -fu origin main"), Safety::RemoteOrDestructive);
+        assert_eq!(safety("git branch -Df topic"), Safety::RemoteOrDestructive);
+        assert_eq!(safety("git clean -fd"), Safety::RemoteOrDestructive);
+        assert_eq!(safety("git clean -nd"), Safety::LocalMutating);
+    }
+
+    #[test]
+    fn dangerous_config_overrides_are_destructive() {
+        assert_eq!(
+            safety("git -c alias.st=!rm status"),
+            Safety::RemoteOrDestructive
+        );
+        assert_eq!(
+            safety("git -c core.sshCommand=evil fetch"),
+            Safety::RemoteOrDestructive
+        );
+        assert_eq!(
+            safety("git -c core.pager=cat log"),
+            Safety::RemoteOrDestructive
+        );
+        assert_eq!(safety("git -c color.ui=never log"), Safety::ReadOnly);
+    }
+
+    #[test]
+    fn only_harmless_config_overrides_are_allowed() {
+        for line in [
+            "git -c alias.st=status st",
+            "git -c pager.log=evil log",
+            "git -c diff.external=evil diff",
+            "git -c credential.helper=!evil fetch",
+            "git -c gpg.program=evil log --show-signature",
+            "git -c sequence.editor=evil rebase -i HEAD~2",
+            "git -c diff.word.textconv=evil diff",
+            "git -c merge.tool.cmd=evil mergetool",
+            "git -c uploadpack.packObjectsHook=evil fetch",
+            "git --config-env=core.editor=EDITOR commit",
+            "git --config-env core.editor=EDITOR commit",
+        ] {
+            assert_eq!(safety(line), Safety::RemoteOrDestructive, "{}", line);
+        }
+
+        assert_eq!(safety("git -c user.email=me@example.com commit -m wip"), Safety::LocalMutating);
+        assert_eq!(safety("git -c log.date=iso -c column.ui=always log"), Safety::ReadOnly);
+    }
+
+    #[test]
+    fn commands_that_throw_work_away_are_destructive() {
+        for line in [
+            "git switch -f main",
+            "git switch --discard-changes main",
+            "git switch -C topic",
+            "git checkout main src/lib.rs",
+            "git checkout HEAD~2 README.md",
+            "git checkout -B topic",
+            "git branch -M main",
+            "git branch -C topic main",
+            "git branch -f topic HEAD~3",
+            "git rm -f src/lib.rs",
+            "git rm -rf src",
+            "git worktree remove --force ../wip",
+            "git tag -f v1.0.0",
+        ] {
+            assert_eq!(safety(line), Safety::RemoteOrDestructive, "{}", line);
+        }
+
+        for line in [
+            "git switch main",
+            "git checkout main",
+            "git checkout -b topic main",
+            "git branch -m old new",
+            "git rm src/lib.rs",
+            "git worktree remove ../wip",
+            "git tag v1.0.0",
+        ] {
+            assert_eq!(safety(line), Safety::LocalMutating, "{}", line);
+        }
+    }
+
+    #[test]
+    fn plain_commands_keep_their_class() {
+        assert_eq!(safety("git status"), Safety::ReadOnly);
+        assert_eq!(safety("git branch -vv"), Safety::ReadOnly);
+        assert_eq!(safety("git commit -m wip"), Safety::LocalMutating);
+        assert_eq!(safety("git push origin main"), Safety::RemoteOrDestructive);
+    }
+}
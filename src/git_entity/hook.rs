@@ -0,0 +1,62 @@
+use crate::error::GitAIError;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone)]
+pub enum HookError {
+    #[error("A {0} hook not managed by gitai already exists at {1}")]
+    AlreadyExists(String, String),
+}
+
+/// Marker written into the hooks installed by gitai, so they can be told apart
+const MANAGED_MARKER: &str = "# Installed by gitai";
+
+pub struct Hook {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+impl Hook {
+    pub fn new(name: &str) -> Result<Self, GitAIError> {
+        let output = Command::new("git")
+            .args(["rev-parse", "--git-path", &format!("hooks/{}", name)])
+            .output()?;
+
+        let path = String::from_utf8(output.stdout)?;
+        Ok(Hook {
+            name: name.to_string(),
+            path: PathBuf::from(path.trim()),
+        })
+    }
+
+    /// Writes an executable `sh` hook running `command`, replacing a previous gitai hook
+    pub fn install(&self, command: &str) -> Result<(), GitAIError> {
+        if let Ok(existing) = fs::read_to_string(&self.path) {
+            if !existing.contains(MANAGED_MARKER) {
+                return Err(HookError::AlreadyExists(
+                    self.name.clone(),
+                    self.path.display().to_string(),
+                )
+                .into());
+            }
+        }
+
+        if let Some(hooks_dir) = self.path.parent() {
+            fs::create_dir_all(hooks_dir)?;
+        }
+        fs::write(
+            &self.path,
+            format!("#!/bin/sh\n{}\nexec {}\n", MANAGED_MARKER, command),
+        )?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&self.path, fs::Permissions::from_mode(0o755))?;
+        }
+
+        Ok(())
+    }
+}
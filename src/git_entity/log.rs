@@ -0,0 +1,178 @@
+use crate::error::GitAIError;
+use serde::Deserialize;
+use std::process::Command;
+
+/// A `git log` search over the repository history, as requested by the model
+/// or constrained by the user on the command line.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct LogQuery {
+    /// Commits that change the number of occurrences of the string (`-S`)
+    pub pickaxe: Option<String>,
+    /// Commits whose diff has added or removed lines matching the regex (`-G`)
+    pub regex: Option<String>,
+    /// Commits whose message matches the pattern (`--grep`)
+    pub grep: Option<String>,
+    pub author: Option<String>,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub paths: Vec<String>,
+    /// Search every ref instead of only HEAD (`--all`)
+    #[serde(skip)]
+    pub all_refs: bool,
+}
+
+impl LogQuery {
+    /// Restricts the search to the user's `constraints`, which win over the model's values.
+    /// Paths are intersected: the model may narrow the user's paths, never widen them.
+    pub fn merged_with(mut self, constraints: &LogQuery) -> Self {
+        self.author = constraints.author.clone().or(self.author);
+        self.since = constraints.since.clone().or(self.since);
+        self.until = constraints.until.clone().or(self.until);
+        self.paths = intersect_paths(&self.paths, &constraints.paths);
+        self
+    }
+
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(pickaxe) = &self.pickaxe {
+            parts.push(format!("-S {:?}", pickaxe));
+        }
+        if let Some(regex) = &self.regex {
+            parts.push(format!("-G {:?}", regex));
+        }
+        if let Some(grep) = &self.grep {
+            parts.push(format!("--grep {:?}", grep));
+        }
+        if let Some(author) = &self.author {
+            parts.push(format!("--author {:?}", author));
+        }
+        if let Some(since) = &self.since {
+            parts.push(format!("--since {:?}", since));
+        }
+        if let Some(until) = &self.until {
+            parts.push(format!("--until {:?}", until));
+        }
+        if !self.paths.is_empty() {
+            parts.push(format!("-- {}", self.paths.join(" ")));
+        }
+        parts.join(" ")
+    }
+
+    /// Runs the search and returns the matching non-merge commits, newest first
+    pub fn run(&self, limit: usize) -> Result<Vec<String>, GitAIError> {
+        let mut args = vec![
+            "log".to_string(),
+            "--no-merges".to_string(),
+            "--format=%H".to_string(),
+            "-n".to_string(),
+            limit.to_string(),
+        ];
+
+        if self.all_refs {
+            args.push("--all".to_string());
+        }
+        if let Some(pickaxe) = &self.pickaxe {
+            args.push(format!("-S{}", pickaxe));
+        }
+        if let Some(regex) = &self.regex {
+            args.push(format!("-G{}", regex));
+        }
+        if let Some(grep) = &self.grep {
+            args.push(format!("--grep={}", grep));
+            args.push("--regexp-ignore-case".to_string());
+        }
+        if let Some(author) = &self.author {
+            args.push(format!("--author={}", author));
+        }
+        if let Some(since) = &self.since {
+            args.push(format!("--since={}", since));
+        }
+        if let Some(until) = &self.until {
+            args.push(format!("--until={}", until));
+        }
+
+        args.push("--".to_string());
+        args.extend(self.paths.iter().cloned());
+
+        let output = Command::new("git").args(&args).output()?;
+
+        let output_str = String::from_utf8(output.stdout)?;
+        Ok(output_str.lines().map(String::from).collect())
+    }
+}
+
+fn contains_path(parent: &str, path: &str) -> bool {
+    let parent = parent.trim_end_matches('/');
+    path == parent || path.starts_with(&format!("{}/", parent))
+}
+
+/// The paths of `proposed` inside `allowed`, and those of `allowed` inside `proposed`;
+/// `allowed` alone when the two do not overlap
+fn intersect_paths(proposed: &[String], allowed: &[String]) -> Vec<String> {
+    if allowed.is_empty() {
+        return proposed.to_vec();
+    }
+
+    let mut paths: Vec<String> = proposed
+        .iter()
+        .filter(|path| allowed.iter().any(|parent| contains_path(parent, path)))
+        .cloned()
+        .collect();
+    for path in allowed {
+        if proposed.iter().any(|parent| contains_path(parent, path)) && !paths.contains(path) {
+            paths.push(path.clone());
+        }
+    }
+
+    if paths.is_empty() {
+        allowed.to_vec()
+    } else {
+        paths
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(paths: &[&str]) -> Vec<String> {
+        paths.iter().map(|path| path.to_string()).collect()
+    }
+
+    #[test]
+    fn user_constraints_win_over_the_model() {
+        let proposed = LogQuery {
+            author: Some("bob".to_string()),
+            since: Some("1 year ago".to_string()),
+            grep: Some("retry".to_string()),
+            ..Default::default()
+        };
+        let constraints = LogQuery {
+            author: Some("alice".to_string()),
+            until: Some("yesterday".to_string()),
+            ..Default::default()
+        };
+
+        let query = proposed.merged_with(&constraints);
+
+        assert_eq!(query.author.as_deref(), Some("alice"));
+        assert_eq!(query.since.as_deref(), Some("1 year ago"));
+        assert_eq!(query.until.as_deref(), Some("yesterday"));
+        assert_eq!(query.grep.as_deref(), Some("retry"));
+    }
+
+    #[test]
+    fn paths_are_intersected() {
+        let allowed = paths(&["src/http"]);
+
+        assert_eq!(
+            intersect_paths(&paths(&["src/http/retry.rs", "src/cli.rs"]), &allowed),
+            paths(&["src/http/retry.rs"])
+        );
+        assert_eq!(intersect_paths(&paths(&["src"]), &allowed), allowed);
+        assert_eq!(intersect_paths(&paths(&["docs"]), &allowed), allowed);
+        assert_eq!(intersect_paths(&paths(&["src/httpd"]), &allowed), allowed);
+        assert_eq!(intersect_paths(&paths(&["docs"]), &[]), paths(&["docs"]));
+    }
+}
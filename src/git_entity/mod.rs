@@ -2,8 +2,15 @@ use commit::Commit;
 use diff::Diff;
 use indoc::formatdoc;
 
+pub mod branch;
 pub mod commit;
+pub mod conflict;
 pub mod diff;
+pub mod git_command;
+pub mod hook;
+pub mod log;
+pub mod rebase;
+pub mod tag;
 
 #[derive(Debug, Clone)]
 pub enum GitEntity {
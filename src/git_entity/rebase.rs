@@ -0,0 +1,73 @@
+use super::conflict::Operation;
+use crate::error::GitAIError;
+use std::fs;
+use std::process::Command;
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone)]
+pub enum RebaseError {
+    #[error("Rebase onto '{0}' failed, the branch was left as it was: {1}")]
+    Failed(String, String),
+}
+
+pub struct Reword {
+    pub full_hash: String,
+    pub message: String,
+}
+
+/// Replays `commits` (oldest first) on top of `base`, the commit they were made on so that
+/// the branch is not moved, replacing the message of every commit listed in `rewords`. The rebase todo list is prepared up front and handed to
+/// `git rebase -i` through `GIT_SEQUENCE_EDITOR`, so no editor is ever opened.
+pub fn reword_commits(base: &str, commits: &[String], rewords: &[Reword]) -> Result<(), GitAIError> {
+    let work_dir = std::env::temp_dir().join(format!("gitai-reword-{}", std::process::id()));
+    fs::create_dir_all(&work_dir)?;
+
+    let result = run_reword(base, commits, rewords, &work_dir);
+
+    let _ = fs::remove_dir_all(&work_dir);
+    result
+}
+
+fn run_reword(
+    base: &str,
+    commits: &[String],
+    rewords: &[Reword],
+    work_dir: &std::path::Path,
+) -> Result<(), GitAIError> {
+    let mut todo = String::new();
+    for (index, full_hash) in commits.iter().enumerate() {
+        todo.push_str(&format!("pick {}\n", full_hash));
+
+        if let Some(reword) = rewords.iter().find(|r| &r.full_hash == full_hash) {
+            let message_path = work_dir.join(format!("{}.msg", index));
+            fs::write(&message_path, &reword.message)?;
+            todo.push_str(&format!(
+                "exec git commit --amend --allow-empty --no-verify -F '{}'\n",
+                message_path.display()
+            ));
+        }
+    }
+
+    let todo_path = work_dir.join("git-rebase-todo");
+    fs::write(&todo_path, todo)?;
+
+    let output = Command::new("git")
+        .args(["rebase", "-i", base])
+        .env(
+            "GIT_SEQUENCE_EDITOR",
+            format!("cp '{}'", todo_path.display()),
+        )
+        .env("GIT_EDITOR", "true")
+        .output()?;
+
+    if !output.status.success() {
+        // A dirty working tree stops the rebase before it starts, there is nothing to abort then
+        if matches!(Operation::detect(), Ok(Operation::Rebase)) {
+            let _ = Command::new("git").args(["rebase", "--abort"]).output();
+        }
+        let stderr = String::from_utf8(output.stderr)?;
+        return Err(RebaseError::Failed(base.to_string(), stderr.trim().to_string()).into());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,217 @@
+use crate::error::GitAIError;
+use std::cmp::Ordering;
+use std::fmt;
+use std::process::Command;
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone)]
+pub enum TagError {
+    #[error("Tag '{0}' already exists")]
+    AlreadyExists(String),
+
+    #[error("Could not create tag '{0}': {1}")]
+    CreateFailed(String, String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Bump {
+    Patch,
+    Minor,
+    Major,
+}
+
+impl fmt::Display for Bump {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Bump::Patch => write!(f, "patch"),
+            Bump::Minor => write!(f, "minor"),
+            Bump::Major => write!(f, "major"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+    pub pre_release: Option<String>,
+}
+
+impl Version {
+    /// Parses `1.2.3`, `v1.2.3` and `1.2.3-rc.1`, ignoring build metadata
+    pub fn parse(tag: &str) -> Option<Self> {
+        let version = tag.strip_prefix('v').unwrap_or(tag);
+        let version = version.split_once('+').map_or(version, |(v, _)| v);
+        let (core, pre_release) = match version.split_once('-') {
+            Some((core, pre_release)) => (core, Some(pre_release.to_string())),
+            None => (version, None),
+        };
+
+        let mut parts = core.split('.').map(|part| part.parse::<u64>().ok());
+        let (Some(Some(major)), Some(Some(minor)), Some(Some(patch)), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return None;
+        };
+
+        Some(Version {
+            major,
+            minor,
+            patch,
+            pre_release,
+        })
+    }
+
+    /// A pre-release is released by the bumps it already carries, `1.2.0-rc.1` gives `1.2.0`
+    /// for a patch or minor bump, but `2.0.0` for a major one
+    pub fn bump(&self, bump: Bump) -> Self {
+        let released = self.pre_release.is_some();
+        let (major, minor, patch) = match bump {
+            Bump::Major if released && self.minor == 0 && self.patch == 0 => (self.major, 0, 0),
+            Bump::Major => (self.major + 1, 0, 0),
+            Bump::Minor if released && self.patch == 0 => (self.major, self.minor, 0),
+            Bump::Minor => (self.major, self.minor + 1, 0),
+            Bump::Patch if released => (self.major, self.minor, self.patch),
+            Bump::Patch => (self.major, self.minor, self.patch + 1),
+        };
+
+        Version {
+            major,
+            minor,
+            patch,
+            pre_release: None,
+        }
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.major, self.minor, self.patch)
+            .cmp(&(other.major, other.minor, other.patch))
+            // A pre-release sorts before the release it leads to
+            .then_with(|| match (&self.pre_release, &other.pre_release) {
+                (None, None) => Ordering::Equal,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(_), None) => Ordering::Less,
+                (Some(a), Some(b)) => compare_pre_releases(a, b),
+            })
+    }
+}
+
+/// SemVer precedence: dot separated identifiers from left to right, numbers by value and
+/// before words, then the shorter list first, so `rc.2 < rc.10 < rc.10.1`
+fn compare_pre_releases(a: &str, b: &str) -> Ordering {
+    let identifier = |part: &str| match part.parse::<u64>() {
+        Ok(number) => (0, number, String::new()),
+        Err(_) => (1, 0, part.to_string()),
+    };
+
+    a.split('.')
+        .map(identifier)
+        .cmp(b.split('.').map(identifier))
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        if let Some(pre_release) = &self.pre_release {
+            write!(f, "-{}", pre_release)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Tag {
+    pub name: String,
+    pub version: Version,
+}
+
+impl Tag {
+    /// The highest semver tag reachable from HEAD
+    pub fn latest_semver() -> Result<Option<Self>, GitAIError> {
+        let output = Command::new("git")
+            .args(["tag", "--merged", "HEAD"])
+            .output()?;
+
+        let output_str = String::from_utf8(output.stdout)?;
+        Ok(output_str
+            .lines()
+            .filter_map(|name| {
+                Version::parse(name).map(|version| Tag {
+                    name: name.to_string(),
+                    version,
+                })
+            })
+            .max_by(|a, b| a.version.cmp(&b.version)))
+    }
+
+    pub fn create_annotated(name: &str, message: &str) -> Result<(), GitAIError> {
+        let exists = Command::new("git")
+            .args([
+                "rev-parse",
+                "--verify",
+                "--quiet",
+                &format!("refs/tags/{}", name),
+            ])
+            .output()?;
+        if exists.status.success() {
+            return Err(TagError::AlreadyExists(name.to_string()).into());
+        }
+
+        let output = Command::new("git")
+            .args(["tag", "-a", name, "-m", message])
+            .output()?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8(output.stderr)?;
+            return Err(TagError::CreateFailed(name.to_string(), stderr.trim().to_string()).into());
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bump(version: &str, bump: Bump) -> String {
+        Version::parse(version).unwrap().bump(bump).to_string()
+    }
+
+    #[test]
+    fn bumps_a_release() {
+        assert_eq!(bump("1.2.3", Bump::Patch), "1.2.4");
+        assert_eq!(bump("1.2.3", Bump::Minor), "1.3.0");
+        assert_eq!(bump("v1.2.3", Bump::Major), "2.0.0");
+    }
+
+    #[test]
+    fn releases_a_pre_release() {
+        assert_eq!(bump("1.2.0-rc.1", Bump::Patch), "1.2.0");
+        assert_eq!(bump("1.2.0-rc.1", Bump::Minor), "1.2.0");
+        assert_eq!(bump("1.2.0-rc.1", Bump::Major), "2.0.0");
+        assert_eq!(bump("1.2.3-rc.1", Bump::Minor), "1.3.0");
+        assert_eq!(bump("2.0.0-beta", Bump::Major), "2.0.0");
+    }
+
+    #[test]
+    fn orders_pre_releases_by_their_identifiers() {
+        let version = |tag: &str| Version::parse(tag).unwrap();
+
+        assert!(version("1.0.0-rc.2") < version("1.0.0-rc.10"));
+        assert!(version("1.0.0-rc.10") < version("1.0.0-rc.10.1"));
+        assert!(version("1.0.0-alpha") < version("1.0.0-alpha.1"));
+        assert!(version("1.0.0-alpha.1") < version("1.0.0-alpha.beta"));
+        assert!(version("1.0.0-beta.11") < version("1.0.0-rc.1"));
+        assert!(version("1.0.0-rc.10") < version("1.0.0"));
+    }
+}
@@ -1,101 +1,329 @@
 use async_trait::async_trait;
+use futures_util::StreamExt;
 use reqwest::StatusCode;
 use serde_json::{json, Value};
 
-use super::{LLMComplete, LLMError, Message, Role};
+use super::http::{self, HttpClient};
+use super::{
+    sse, usage, GenerationParams, LLMComplete, LLMError, Message, ModelInfo, Role, TextStream,
+};
 #[derive(Clone)]
 pub struct AnthropicConfig {
     api_key: String,
     model: String,
     api_base_url: String,
+    models_url: String,
 }
 
 impl AnthropicConfig {
-    pub fn new(api_key: String, model: Option<String>) -> Self {
+    pub fn new(api_key: String, model: Option<String>, base_url: Option<String>) -> Self {
+        let base_url = base_url.unwrap_or("https://api.anthropic.com/v1".to_string());
+        let base_url = base_url.trim_end_matches('/');
+
         AnthropicConfig {
             api_key,
-            model: model.unwrap_or("gpt-4o-mini".to_string()),
-            api_base_url: "https://api.openai.com/v1/chat/completions".to_string(),
+            model: model.unwrap_or("claude-3-5-sonnet-latest".to_string()),
+            api_base_url: format!("{}/messages", base_url),
+            models_url: format!("{}/models", base_url),
         }
     }
 }
 #[derive(Clone)]
 pub struct AnthropicProvider {
     config: AnthropicConfig,
-    client: reqwest::Client,
+    client: HttpClient,
 }
 
 impl AnthropicProvider {
-    pub fn new(client: reqwest::Client, config: AnthropicConfig) -> Self {
+    pub fn new(client: HttpClient, config: AnthropicConfig) -> Self {
         AnthropicProvider { client, config }
     }
 
-    pub async fn complete(&self, messages: &[Message]) -> Result<String, LLMError> {
+    fn build_payload(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+        stream: bool,
+    ) -> Result<Value, LLMError> {
+        // The system prompt is a top level field, the history only holds user and assistant turns
         let system_prompt = messages
             .iter()
-            .find(|message| message.role == Role::System)
-            .map(|message| message.content.clone())
-            .unwrap_or("".to_string());
+            .filter(|message| message.role == Role::System)
+            .map(|message| message.content.as_str())
+            .collect::<Vec<_>>()
+            .join("\n\n");
+
+        let history = messages
+            .iter()
+            .filter_map(|message| {
+                let role = match message.role {
+                    Role::System => return None,
+                    Role::User => "user",
+                    Role::Assistant => "assistant",
+                };
+                Some(json!({
+                    "role": role,
+                    "content": message.content,
+                }))
+            })
+            .collect::<Vec<Value>>();
 
-        let user_mesage = messages.get(1).ok_or(LLMError::SomeError)?;
+        if history.is_empty() {
+            return Err(LLMError::SomeError);
+        }
 
-        let payload = json!({
+        params.warn_unsupported("Anthropic", &["temperature", "top_p", "max_tokens", "stop"]);
+
+        // The Messages API requires a limit
+        let mut payload = json!({
             "model": self.config.model,
-            "max_tokens": 4096,
-            "system": system_prompt,
-            "messages": [
-                {
-                    "role": "user",
-                    "content": user_mesage.content
-                }
-            ]
+            "max_tokens": params.max_tokens.unwrap_or(4096),
+            "messages": history,
+            "stream": stream,
         });
 
+        if !system_prompt.is_empty() {
+            payload["system"] = json!(system_prompt);
+        }
+        if let Some(temperature) = params.temperature {
+            payload["temperature"] = json!(temperature);
+        }
+        if let Some(top_p) = params.top_p {
+            payload["top_p"] = json!(top_p);
+        }
+        if !params.stop.is_empty() {
+            payload["stop_sequences"] = json!(params.stop);
+        }
+        // A forced tool call is how Claude answers with an object of a given schema
+        if let Some(response_schema) = &params.response_schema {
+            payload["tools"] = json!([{
+                "name": response_schema.name,
+                "description": "Answer with this object",
+                "input_schema": response_schema.schema,
+            }]);
+            payload["tool_choice"] = json!({ "type": "tool", "name": response_schema.name });
+        }
+
+        Ok(payload)
+    }
+
+    /// Maps Anthropic's typed error objects, `{"type": "error", "error": {"type": ..., "message": ...}}`
+    fn parse_error(status: StatusCode, error_json: &Value) -> Option<LLMError> {
+        let error = error_json.get("error");
+        let message = error
+            .and_then(|error| error.get("message"))
+            .and_then(|msg| msg.as_str())
+            .unwrap_or_default()
+            .to_string();
+
+        let error = match error?.get("type")?.as_str()? {
+            "authentication_error" | "permission_error" => LLMError::AuthError(message),
+            "rate_limit_error" => LLMError::RateLimited(message),
+            "overloaded_error" => LLMError::Overloaded(message),
+            "invalid_request_error" | "not_found_error" | "request_too_large" => {
+                LLMError::InvalidRequest(message)
+            }
+            _ => http::status_error(status, message),
+        };
+        Some(error)
+    }
+
+    /// `end_turn` and `stop_sequence` are normal ends, the others leave the answer unusable
+    fn check_stop_reason(stop_reason: Option<&str>) -> Result<(), LLMError> {
+        match stop_reason {
+            Some("max_tokens") => Err(LLMError::Incomplete(
+                "the response reached the maximum number of tokens".to_string(),
+            )),
+            Some("refusal") => Err(LLMError::Incomplete(
+                "the model declined to answer".to_string(),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    async fn send(&self, payload: &Value) -> Result<reqwest::Response, LLMError> {
         let response = self
             .client
-            .post(&self.config.api_base_url)
-            .header("x-api-key", &self.config.api_key)
-            .header("anthropic-version", "2023-06-01")
-            .header("Content-Type", "application/json")
-            .json(&payload)
-            .send()
+            .send(|client| {
+                client
+                    .post(&self.config.api_base_url)
+                    .header("x-api-key", &self.config.api_key)
+                    .header("anthropic-version", "2023-06-01")
+                    .header("Content-Type", "application/json")
+                    .json(payload)
+            })
             .await?;
 
         let status = response.status();
 
         match status {
-            StatusCode::OK => {
-                let response_json: Value = response.json().await?;
-
-                let content = response_json
-                    .get("content")
-                    .and_then(|content| content.get(0))
-                    .and_then(|choice| choice.get("message"))
-                    .and_then(|message| message.get("text"))
-                    .and_then(|text| text.as_str())
-                    .ok_or(LLMError::NoCompletionChoice)?;
-
-                Ok(content.to_string())
-            }
-            _ => {
-                let error_json: Value = response.json().await?;
+            StatusCode::OK => Ok(response),
+            _ => Err(http::response_error(response, Self::parse_error).await),
+        }
+    }
 
-                let error_message = error_json
-                    .get("error")
-                    .and_then(|error| error.get("message"))
-                    .and_then(|msg| msg.as_str())
-                    .ok_or(LLMError::UnexpectedResponse)?
-                    .into();
+    /// Text, or the JSON of a tool call, arrives in `content_block_delta` events, the end of the
+    /// message in `message_delta`
+    fn parse_event(data: &str, model: &str) -> Option<Result<String, LLMError>> {
+        let json_value: Value = serde_json::from_str(data).ok()?;
+
+        match json_value.get("type")?.as_str()? {
+            // The input tokens are counted when the message starts, the output ones when it ends
+            "message_start" => {
+                let input_tokens = json_value
+                    .get("message")?
+                    .get("usage")?
+                    .get("input_tokens")?
+                    .as_u64()?;
+                usage::record(model, input_tokens, 0);
+                None
+            }
+            "content_block_delta" => {
+                let delta = json_value.get("delta")?;
+                delta
+                    .get("text")
+                    .or_else(|| delta.get("partial_json"))?
+                    .as_str()
+                    .map(|text| Ok(text.to_string()))
+            }
+            "message_delta" => {
+                if let Some(output_tokens) = json_value
+                    .get("usage")
+                    .and_then(|usage| usage.get("output_tokens"))
+                    .and_then(Value::as_u64)
+                {
+                    usage::record(model, 0, output_tokens);
+                }
 
-                Err(LLMError::APIError(status, error_message))
+                let stop_reason = json_value.get("delta")?.get("stop_reason")?.as_str();
+                Self::check_stop_reason(stop_reason).err().map(Err)
             }
+            "error" => {
+                let error = Self::parse_error(StatusCode::OK, &json_value);
+                Some(Err(error.unwrap_or(LLMError::UnexpectedResponse)))
+            }
+            _ => None,
+        }
+    }
+
+    pub async fn complete(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<String, LLMError> {
+        let response = self.send(&self.build_payload(messages, params, false)?).await?;
+        let response_json: Value = response.json().await?;
+
+        if let Some(response_usage) = response_json.get("usage") {
+            usage::record(
+                &self.config.model,
+                response_usage
+                    .get("input_tokens")
+                    .and_then(Value::as_u64)
+                    .unwrap_or_default(),
+                response_usage
+                    .get("output_tokens")
+                    .and_then(Value::as_u64)
+                    .unwrap_or_default(),
+            );
         }
+
+        Self::check_stop_reason(
+            response_json
+                .get("stop_reason")
+                .and_then(|stop_reason| stop_reason.as_str()),
+        )?;
+
+        let content = response_json
+            .get("content")
+            .and_then(|content| content.as_array())
+            .ok_or(LLMError::UnexpectedResponse)?
+            .iter()
+            .filter_map(|block| match block.get("type").and_then(|t| t.as_str()) {
+                Some("text") => block.get("text").and_then(|text| text.as_str()).map(String::from),
+                // The object of the tool call forced for a response schema
+                Some("tool_use") => block.get("input").map(Value::to_string),
+                _ => None,
+            })
+            .collect::<String>();
+
+        if content.is_empty() {
+            return Err(LLMError::NoCompletionChoice);
+        }
+
+        Ok(content)
+    }
+
+    pub async fn complete_stream(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<TextStream, LLMError> {
+        let response = self.send(&self.build_payload(messages, params, true)?).await?;
+        let model = self.config.model.clone();
+
+        Ok(sse::data_events(response)
+            .filter_map(move |event| {
+                let model = model.clone();
+                async move {
+                    match event {
+                        Ok(data) => Self::parse_event(&data, &model),
+                        Err(e) => Some(Err(e)),
+                    }
+                }
+            })
+            .boxed())
+    }
+
+    /// One page of up to 1000 models holds them all
+    pub async fn list_models(&self) -> Result<Vec<ModelInfo>, LLMError> {
+        let response_json = self
+            .client
+            .get_json(|client| {
+                client
+                    .get(&self.config.models_url)
+                    .header("x-api-key", &self.config.api_key)
+                    .header("anthropic-version", "2023-06-01")
+                    .query(&[("limit", "1000")])
+            })
+            .await?;
+
+        let models = response_json
+            .get("data")
+            .and_then(|data| data.as_array())
+            .ok_or(LLMError::UnexpectedResponse)?;
+
+        Ok(models
+            .iter()
+            .filter_map(|model| {
+                Some(ModelInfo {
+                    id: model.get("id")?.as_str()?.to_string(),
+                    context_window: model.get("max_input_tokens").and_then(Value::as_u64),
+                })
+            })
+            .collect())
     }
 }
 
 #[async_trait]
 impl LLMComplete for AnthropicProvider {
-    async fn complete(&self, messages: &[Message]) -> Result<String, LLMError> {
-        self.complete(&messages).await
+    async fn complete(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<String, LLMError> {
+        self.complete(messages, params).await
+    }
+
+    async fn complete_stream(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<TextStream, LLMError> {
+        self.complete_stream(messages, params).await
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, LLMError> {
+        self.list_models().await
     }
 }
@@ -0,0 +1,258 @@
+use super::http::{self, HttpClient};
+use super::{sse, usage, GenerationParams, LLMComplete, LLMError, Message, Role, TextStream};
+use async_trait::async_trait;
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use tokio::process::Command;
+
+/// How requests to the Azure resource are authenticated
+#[derive(Clone)]
+pub enum AzureAuth {
+    /// The resource key, sent in the `api-key` header
+    ApiKey(String),
+    /// A shell command printing a Microsoft Entra ID token, e.g. `az account get-access-token ...`
+    TokenCommand(String),
+}
+
+#[derive(Clone)]
+pub struct AzureConfig {
+    auth: AzureAuth,
+    /// Stands for the model in the usage when a response does not name it
+    deployment: String,
+    api_base_url: String,
+    api_version: String,
+}
+
+impl AzureConfig {
+    pub fn new(
+        auth: AzureAuth,
+        endpoint: String,
+        deployment: String,
+        api_version: Option<String>,
+    ) -> Self {
+        Self {
+            auth,
+            api_base_url: format!(
+                "{}/openai/deployments/{}/chat/completions",
+                endpoint.trim_end_matches('/'),
+                deployment
+            ),
+            deployment,
+            api_version: api_version.unwrap_or_else(|| "2024-10-21".to_string()),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct AzureProvider {
+    client: HttpClient,
+    config: AzureConfig,
+}
+
+impl AzureProvider {
+    pub fn new(client: HttpClient, config: AzureConfig) -> Self {
+        Self { client, config }
+    }
+
+    /// The model is picked by the deployment in the URL, not by the payload
+    fn build_payload(messages: &[Message], params: &GenerationParams, stream: bool) -> Value {
+        let mut payload = json!({
+            "messages": messages.iter().map(|message| {
+                json!({
+                    "role": match message.role {
+                        Role::System => "system",
+                        Role::User => "user",
+                        Role::Assistant => "assistant",
+                    },
+                    "content": message.content,
+                })
+            }).collect::<Vec<Value>>(),
+            "stream": stream,
+        });
+
+        if let Some(temperature) = params.temperature {
+            payload["temperature"] = json!(temperature);
+        }
+        if let Some(top_p) = params.top_p {
+            payload["top_p"] = json!(top_p);
+        }
+        if let Some(max_tokens) = params.max_tokens {
+            payload["max_tokens"] = json!(max_tokens);
+        }
+        if !params.stop.is_empty() {
+            payload["stop"] = json!(params.stop);
+        }
+        if let Some(seed) = params.seed {
+            payload["seed"] = json!(seed);
+        }
+        if let Some(response_schema) = &params.response_schema {
+            payload["response_format"] = json!({
+                "type": "json_schema",
+                "json_schema": {
+                    "name": response_schema.name,
+                    "schema": response_schema.schema,
+                    "strict": true,
+                },
+            });
+        }
+
+        // Without it streams do not report their usage
+        if stream {
+            payload["stream_options"] = json!({ "include_usage": true });
+        }
+
+        payload
+    }
+
+    async fn run_token_command(command: &str) -> Result<String, LLMError> {
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .output()
+            .await
+            .map_err(|e| LLMError::AuthError(format!("could not run the token command: {}", e)))?;
+
+        let token = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if !output.status.success() || token.is_empty() {
+            return Err(LLMError::AuthError(format!(
+                "the token command failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        Ok(token)
+    }
+
+    /// Azure errors are `{"error": {"code": ..., "message": ...}}`, except for some gateway
+    /// failures that are `{"statusCode": ..., "message": ...}`
+    fn parse_error(status: StatusCode, error_json: &Value) -> Option<LLMError> {
+        let error = error_json.get("error").unwrap_or(error_json);
+        let message = error
+            .get("message")
+            .and_then(|msg| msg.as_str())
+            .unwrap_or_default()
+            .to_string();
+        let code = error.get("code").and_then(|code| code.as_str());
+
+        let error = match (status, code) {
+            (_, Some("content_filter")) => LLMError::SafetyBlocked(message),
+            (StatusCode::NOT_FOUND, Some("DeploymentNotFound")) => {
+                LLMError::InvalidRequest(message)
+            }
+            _ if message.is_empty() => return None,
+            _ => http::status_error(status, message),
+        };
+        Some(error)
+    }
+
+    async fn send(&self, payload: &Value) -> Result<reqwest::Response, LLMError> {
+        let (auth_header, auth_value) = match &self.config.auth {
+            AzureAuth::ApiKey(api_key) => ("api-key", api_key.clone()),
+            AzureAuth::TokenCommand(command) => (
+                "Authorization",
+                format!("Bearer {}", Self::run_token_command(command).await?),
+            ),
+        };
+
+        let response = self
+            .client
+            .send(|client| {
+                client
+                    .post(&self.config.api_base_url)
+                    .query(&[("api-version", &self.config.api_version)])
+                    .header(auth_header, &auth_value)
+                    .json(payload)
+            })
+            .await?;
+
+        let status = response.status();
+        match status {
+            StatusCode::OK => Ok(response),
+            _ => Err(http::response_error(response, Self::parse_error).await),
+        }
+    }
+
+    /// The first chunk only carries the prompt filter results and has no choices
+    fn parse_delta(data: &str, deployment: &str) -> Option<String> {
+        let json_value: Value = serde_json::from_str(data).ok()?;
+        // The usage comes in a last chunk without choices
+        usage::record_openai(&json_value, deployment);
+        json_value
+            .get("choices")?
+            .get(0)?
+            .get("delta")?
+            .get("content")?
+            .as_str()
+            .map(String::from)
+    }
+
+    async fn complete(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<String, LLMError> {
+        let response = self.send(&Self::build_payload(messages, params, false)).await?;
+        let response_json: Value = response.json().await?;
+        usage::record_openai(&response_json, &self.config.deployment);
+
+        let choice = response_json
+            .get("choices")
+            .and_then(|choices| choices.get(0))
+            .ok_or(LLMError::NoCompletionChoice)?;
+
+        match choice
+            .get("finish_reason")
+            .and_then(|reason| reason.as_str())
+        {
+            Some("content_filter") => {
+                return Err(LLMError::SafetyBlocked(
+                    "the response was filtered by Azure content filtering".to_string(),
+                ))
+            }
+            Some("length") => {
+                return Err(LLMError::Incomplete(
+                    "the response reached the maximum number of tokens".to_string(),
+                ))
+            }
+            _ => {}
+        }
+
+        let content = choice
+            .get("message")
+            .and_then(|message| message.get("content"))
+            .and_then(|content| content.as_str())
+            .ok_or(LLMError::NoCompletionChoice)?;
+
+        Ok(content.to_string())
+    }
+
+    async fn complete_stream(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<TextStream, LLMError> {
+        let response = self.send(&Self::build_payload(messages, params, true)).await?;
+        let deployment = self.config.deployment.clone();
+
+        Ok(sse::text_deltas(response, move |data| Self::parse_delta(data, &deployment)))
+    }
+}
+
+#[async_trait]
+impl LLMComplete for AzureProvider {
+    async fn complete(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<String, LLMError> {
+        self.complete(messages, params).await
+    }
+
+    async fn complete_stream(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<TextStream, LLMError> {
+        self.complete_stream(messages, params).await
+    }
+}
@@ -0,0 +1,299 @@
+use super::{
+    GenerationParams, LLMComplete, LLMError, LLMProvider, Message, ModelInfo, Role, TextStream,
+};
+use async_trait::async_trait;
+use futures_util::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub const DEFAULT_TTL: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+pub const DEFAULT_MAX_BYTES: u64 = 50 * 1024 * 1024;
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    /// Seconds since the Unix epoch
+    created: u64,
+    response: String,
+}
+
+pub struct CacheStats {
+    pub entries: usize,
+    pub expired: usize,
+    pub bytes: u64,
+}
+
+/// Responses stored on disk, one JSON file per request.
+///
+/// The modification time of a file is its last use, which drives the LRU eviction.
+#[derive(Clone)]
+pub struct ResponseCache {
+    dir: PathBuf,
+    ttl: Duration,
+    max_bytes: u64,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// FNV-1a, 128 bits: stable across Rust versions unlike `DefaultHasher`
+pub(super) fn fnv1a_128(parts: &[&[u8]]) -> u128 {
+    const OFFSET_BASIS: u128 = 0x6c62272e07bb014262b821756295c58d;
+    const PRIME: u128 = 0x0000000001000000000000000000013B;
+
+    let mut hash = OFFSET_BASIS;
+    for part in parts {
+        // Length prefixes keep ("ab", "c") and ("a", "bc") apart
+        for byte in (part.len() as u64).to_le_bytes().iter().chain(part.iter()) {
+            hash ^= *byte as u128;
+            hash = hash.wrapping_mul(PRIME);
+        }
+    }
+    hash
+}
+
+impl ResponseCache {
+    pub fn new(dir: PathBuf, ttl: Duration, max_bytes: u64) -> Self {
+        Self {
+            dir,
+            ttl,
+            max_bytes,
+        }
+    }
+
+    /// The key of a request to the provider and model described by `identity`, a different
+    /// temperature or token limit is a different request
+    pub fn key(identity: &str, messages: &[Message], params: &GenerationParams) -> String {
+        let params = serde_json::to_string(params).unwrap_or_default();
+        let mut parts: Vec<&[u8]> = vec![identity.as_bytes(), params.as_bytes()];
+        for message in messages {
+            parts.push(match message.role {
+                Role::System => b"system",
+                Role::User => b"user",
+                Role::Assistant => b"assistant",
+            });
+            parts.push(message.content.as_bytes());
+        }
+
+        format!("{:032x}", fnv1a_128(&parts))
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    fn is_expired(&self, entry: &CacheEntry) -> bool {
+        now().saturating_sub(entry.created) > self.ttl.as_secs()
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        let path = self.entry_path(key);
+        let entry: CacheEntry = serde_json::from_str(&fs::read_to_string(&path).ok()?).ok()?;
+
+        if self.is_expired(&entry) {
+            let _ = fs::remove_file(&path);
+            return None;
+        }
+
+        // Marks the entry as recently used
+        if let Ok(file) = File::options().append(true).open(&path) {
+            let _ = file.set_modified(SystemTime::now());
+        }
+
+        Some(entry.response)
+    }
+
+    /// Stores a response, a cache that cannot be written only costs a new request next time
+    pub fn put(&self, key: &str, response: &str) {
+        let entry = CacheEntry {
+            created: now(),
+            response: response.to_string(),
+        };
+
+        let written = fs::create_dir_all(&self.dir).and_then(|_| {
+            let content = serde_json::to_string(&entry).map_err(io::Error::other)?;
+            fs::write(self.entry_path(key), content)
+        });
+
+        if written.is_ok() {
+            let _ = self.evict();
+        }
+    }
+
+    fn entries(&self) -> io::Result<Vec<(PathBuf, u64, SystemTime)>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == "json")
+            {
+                let metadata = fs::metadata(&path)?;
+                entries.push((path, metadata.len(), metadata.modified()?));
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Removes the least recently used entries until the cache fits in its size cap
+    fn evict(&self) -> io::Result<()> {
+        let mut entries = self.entries()?;
+        let mut total: u64 = entries.iter().map(|(_, size, _)| size).sum();
+
+        entries.sort_by_key(|(_, _, modified)| *modified);
+        for (path, size, _) in entries {
+            if total <= self.max_bytes {
+                break;
+            }
+            fs::remove_file(path)?;
+            total -= size;
+        }
+        Ok(())
+    }
+
+    pub fn stats(&self) -> io::Result<CacheStats> {
+        let entries = self.entries()?;
+        let expired = entries
+            .iter()
+            .filter_map(|(path, _, _)| fs::read_to_string(path).ok())
+            .filter_map(|content| serde_json::from_str::<CacheEntry>(&content).ok())
+            .filter(|entry| self.is_expired(entry))
+            .count();
+
+        Ok(CacheStats {
+            entries: entries.len(),
+            expired,
+            bytes: entries.iter().map(|(_, size, _)| size).sum(),
+        })
+    }
+
+    /// Removes every entry, returning how many there were
+    pub fn clear(&self) -> io::Result<usize> {
+        let entries = self.entries()?;
+        for (path, _, _) in &entries {
+            fs::remove_file(path)?;
+        }
+        Ok(entries.len())
+    }
+
+    pub fn dir(&self) -> &PathBuf {
+        &self.dir
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    pub fn max_bytes(&self) -> u64 {
+        self.max_bytes
+    }
+}
+
+/// A provider whose responses are answered from the cache when the same request was made before
+#[derive(Clone)]
+pub struct CachedProvider {
+    inner: Box<LLMProvider>,
+    cache: ResponseCache,
+    /// The provider, model and settings that the response depends on
+    identity: String,
+}
+
+struct CachingStream {
+    stream: TextStream,
+    text: String,
+    cache: ResponseCache,
+    key: String,
+}
+
+impl CachedProvider {
+    pub fn new(inner: LLMProvider, cache: ResponseCache, identity: String) -> Self {
+        Self {
+            inner: Box::new(inner),
+            cache,
+            identity,
+        }
+    }
+
+    async fn complete(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<String, LLMError> {
+        let key = ResponseCache::key(&self.identity, messages, params);
+        if let Some(response) = self.cache.get(&key) {
+            return Ok(response);
+        }
+
+        let response = self.inner.complete(messages, params).await?;
+        self.cache.put(&key, &response);
+        Ok(response)
+    }
+
+    /// Stores the streamed text once the stream ends, a stream that fails is not cached
+    async fn complete_stream(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<TextStream, LLMError> {
+        let key = ResponseCache::key(&self.identity, messages, params);
+        if let Some(response) = self.cache.get(&key) {
+            return Ok(stream::once(async move { Ok(response) }).boxed());
+        }
+
+        let state = CachingStream {
+            stream: self.inner.complete_stream(messages, params).await?,
+            text: String::new(),
+            cache: self.cache.clone(),
+            key,
+        };
+
+        Ok(stream::unfold(Some(state), |state| async move {
+            let mut state = state?;
+            match state.stream.next().await {
+                Some(Ok(delta)) => {
+                    state.text.push_str(&delta);
+                    Some((Ok(delta), Some(state)))
+                }
+                Some(Err(e)) => Some((Err(e), None)),
+                None => {
+                    state.cache.put(&state.key, &state.text);
+                    None
+                }
+            }
+        })
+        .boxed())
+    }
+}
+
+#[async_trait]
+impl LLMComplete for CachedProvider {
+    async fn complete(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<String, LLMError> {
+        self.complete(messages, params).await
+    }
+
+    async fn complete_stream(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<TextStream, LLMError> {
+        self.complete_stream(messages, params).await
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, LLMError> {
+        self.inner.list_models().await
+    }
+}
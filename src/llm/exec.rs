@@ -0,0 +1,246 @@
+use super::{GenerationParams, LLMComplete, LLMError, Message, TextStream};
+use async_trait::async_trait;
+use futures_util::stream::{self, StreamExt};
+use std::io;
+use std::process::{ExitStatus, Stdio};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdout, Command};
+use tokio::task::JoinHandle;
+
+#[derive(Clone)]
+pub struct ExecConfig {
+    command: String,
+    model: Option<String>,
+}
+
+impl ExecConfig {
+    pub fn new(command: String, model: Option<String>) -> Self {
+        Self { command, model }
+    }
+}
+
+/// Runs a shell command for every request: the messages are written to its stdin as JSON,
+/// e.g. `[{"role": "system", "content": "..."}]`, and its stdout is the response
+#[derive(Clone)]
+pub struct ExecProvider {
+    config: ExecConfig,
+}
+
+struct ExecStream {
+    child: Child,
+    stdout: BufReader<ChildStdout>,
+    stderr: JoinHandle<String>,
+}
+
+impl ExecProvider {
+    pub fn new(config: ExecConfig) -> Self {
+        Self { config }
+    }
+
+    /// The parameters go through the environment so that commands which only want the
+    /// messages can ignore them
+    fn spawn(&self, params: &GenerationParams, stream: bool) -> Result<Child, LLMError> {
+        let mut command = Command::new("sh");
+        command
+            .arg("-c")
+            .arg(&self.config.command)
+            .env("GITAI_PARAMS", serde_json::to_string(params).unwrap_or_default())
+            .env("GITAI_STREAM", if stream { "1" } else { "0" })
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        if let Some(model) = &self.config.model {
+            command.env("GITAI_MODEL", model);
+        }
+
+        command.spawn().map_err(|e| {
+            LLMError::CommandFailed(format!("could not run '{}': {}", self.config.command, e))
+        })
+    }
+
+    /// Written from a task, a command may start answering before it has read all of its input
+    fn write_messages(child: &mut Child, messages: &[Message]) -> Result<(), LLMError> {
+        let input = serde_json::to_vec(messages).map_err(|_| LLMError::SomeError)?;
+        let mut stdin = child.stdin.take().ok_or(LLMError::SomeError)?;
+
+        tokio::spawn(async move {
+            // A command that does not read its input closes the pipe, that is its right
+            let _ = stdin.write_all(&input).await;
+        });
+        Ok(())
+    }
+
+    fn check_status(status: ExitStatus, stderr: &str) -> Result<(), LLMError> {
+        if status.success() {
+            return Ok(());
+        }
+
+        let stderr = stderr.trim();
+        Err(LLMError::CommandFailed(if stderr.is_empty() {
+            format!("the command exited with {}", status)
+        } else {
+            format!("the command exited with {}: {}", status, stderr)
+        }))
+    }
+
+    fn read_error(e: io::Error) -> LLMError {
+        LLMError::CommandFailed(format!("could not read the command output: {}", e))
+    }
+
+    async fn complete(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<String, LLMError> {
+        let mut child = self.spawn(params, false)?;
+        Self::write_messages(&mut child, messages)?;
+
+        let output = child.wait_with_output().await.map_err(Self::read_error)?;
+        Self::check_status(output.status, &String::from_utf8_lossy(&output.stderr))?;
+
+        let content = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if content.is_empty() {
+            return Err(LLMError::NoCompletionChoice);
+        }
+        Ok(content)
+    }
+
+    /// Every line the command prints is a delta, with its line ending as printed, the exit
+    /// status is checked once stdout closes
+    async fn complete_stream(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<TextStream, LLMError> {
+        let mut child = self.spawn(params, true)?;
+        Self::write_messages(&mut child, messages)?;
+
+        let stdout = child.stdout.take().ok_or(LLMError::SomeError)?;
+        // Read along the way, a command writing a lot to stderr would block on a full pipe
+        let mut stderr = child.stderr.take().ok_or(LLMError::SomeError)?;
+        let stderr = tokio::spawn(async move {
+            let mut text = String::new();
+            let _ = stderr.read_to_string(&mut text).await;
+            text
+        });
+
+        let state = ExecStream {
+            child,
+            stdout: BufReader::new(stdout),
+            stderr,
+        };
+
+        Ok(stream::unfold(Some(state), |state| async move {
+            let mut state = state?;
+            let mut line = Vec::new();
+            match state.stdout.read_until(b'\n', &mut line).await {
+                Ok(0) => {
+                    let status = match state.child.wait().await {
+                        Ok(status) => status,
+                        Err(e) => return Some((Err(Self::read_error(e)), None)),
+                    };
+                    let stderr = state.stderr.await.unwrap_or_default();
+                    Self::check_status(status, &stderr)
+                        .err()
+                        .map(|e| (Err(e), None))
+                }
+                // Lines end on a newline, a character is never split between two deltas
+                Ok(_) => Some((Ok(String::from_utf8_lossy(&line).into_owned()), Some(state))),
+                Err(e) => Some((Err(Self::read_error(e)), None)),
+            }
+        })
+        .boxed())
+    }
+}
+
+#[async_trait]
+impl LLMComplete for ExecProvider {
+    async fn complete(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<String, LLMError> {
+        self.complete(messages, params).await
+    }
+
+    async fn complete_stream(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<TextStream, LLMError> {
+        self.complete_stream(messages, params).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::Role;
+    use std::time::Duration;
+
+    fn provider(command: &str) -> ExecProvider {
+        ExecProvider::new(ExecConfig::new(command.to_string(), Some("stand-in".to_string())))
+    }
+
+    fn messages() -> Vec<Message> {
+        vec![Message {
+            role: Role::User,
+            content: "Hi".to_string(),
+        }]
+    }
+
+    #[tokio::test]
+    async fn answers_with_what_the_command_prints() {
+        let response = provider(r#"grep -q '"content":"Hi"' && echo "Hello from $GITAI_MODEL""#)
+            .complete(&messages(), &GenerationParams::default())
+            .await;
+
+        assert_eq!(response.unwrap(), "Hello from stand-in");
+    }
+
+    #[tokio::test]
+    async fn fails_with_the_stderr_of_a_failed_command() {
+        let response = provider("cat >/dev/null; echo 'no credentials' >&2; exit 3")
+            .complete(&messages(), &GenerationParams::default())
+            .await;
+
+        match response {
+            Err(LLMError::CommandFailed(message)) => {
+                assert!(message.ends_with(": no credentials"), "{}", message)
+            }
+            other => panic!("not a command failure: {:?}", other.map_err(|e| e.to_string())),
+        }
+    }
+
+    #[tokio::test]
+    async fn streams_each_line_as_printed() {
+        let mut stream = provider(r"printf 'one\r\n'; sleep 1; printf 'two\nthree'")
+            .complete_stream(&messages(), &GenerationParams::default())
+            .await
+            .unwrap();
+
+        // The first line arrives while the command is still running
+        let first = tokio::time::timeout(Duration::from_millis(800), stream.next()).await;
+        assert_eq!(first.unwrap().unwrap().unwrap(), "one\r\n");
+
+        let rest = stream.map(Result::unwrap).collect::<Vec<_>>().await;
+        assert_eq!(rest, ["two\n", "three"]);
+    }
+
+    #[tokio::test]
+    async fn fails_a_stream_when_the_command_fails() {
+        let stream = provider("echo partial; echo 'rate limited' >&2; exit 1")
+            .complete_stream(&messages(), &GenerationParams::default())
+            .await
+            .unwrap();
+
+        let deltas = stream.collect::<Vec<_>>().await;
+
+        assert_eq!(deltas[0].as_deref().ok(), Some("partial\n"));
+        assert!(matches!(
+            &deltas[1],
+            Err(LLMError::CommandFailed(message)) if message.ends_with(": rate limited")
+        ));
+    }
+}
@@ -0,0 +1,117 @@
+use super::{
+    GenerationParams, LLMComplete, LLMError, LLMProvider, Message, ModelInfo, TextStream,
+};
+use async_trait::async_trait;
+use reqwest::StatusCode;
+
+/// Providers tried in order, until one of them answers
+#[derive(Clone)]
+pub struct FallbackChain {
+    /// Each provider with the `provider (model)` name reported when it answers
+    providers: Vec<(String, LLMProvider)>,
+}
+
+impl FallbackChain {
+    pub fn new(providers: Vec<(String, LLMProvider)>) -> Self {
+        Self { providers }
+    }
+
+    /// Failures of the provider rather than of the request, which another provider may not have
+    fn should_fall_back(error: &LLMError) -> bool {
+        match error {
+            LLMError::RequestError(_)
+            | LLMError::Timeout(_)
+            | LLMError::RateLimited(_)
+            | LLMError::AuthError(_)
+            | LLMError::Overloaded(_) => true,
+            LLMError::APIError(status, _) => {
+                *status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+            }
+            _ => false,
+        }
+    }
+
+    /// The models of the primary provider, which the configured model belongs to
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, LLMError> {
+        let (_, primary) = self.providers.first().ok_or(LLMError::SomeError)?;
+        primary.list_models().await
+    }
+
+    fn report(&self, index: usize) {
+        if index > 0 {
+            eprintln!("Answered by the fallback provider {}", self.providers[index].0);
+        }
+    }
+
+    async fn complete(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<String, LLMError> {
+        let mut last_error = LLMError::SomeError;
+
+        for (index, (name, provider)) in self.providers.iter().enumerate() {
+            match provider.complete(messages, params).await {
+                Ok(response) => {
+                    self.report(index);
+                    return Ok(response);
+                }
+                Err(e) if Self::should_fall_back(&e) => {
+                    eprintln!("{} failed: {}", name, e);
+                    last_error = e;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(last_error)
+    }
+
+    /// Falls back only until a stream is opened, a stream that breaks halfway is not replayed
+    async fn complete_stream(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<TextStream, LLMError> {
+        let mut last_error = LLMError::SomeError;
+
+        for (index, (name, provider)) in self.providers.iter().enumerate() {
+            match provider.complete_stream(messages, params).await {
+                Ok(stream) => {
+                    self.report(index);
+                    return Ok(stream);
+                }
+                Err(e) if Self::should_fall_back(&e) => {
+                    eprintln!("{} failed: {}", name, e);
+                    last_error = e;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(last_error)
+    }
+}
+
+#[async_trait]
+impl LLMComplete for FallbackChain {
+    async fn complete(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<String, LLMError> {
+        self.complete(messages, params).await
+    }
+
+    async fn complete_stream(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<TextStream, LLMError> {
+        self.complete_stream(messages, params).await
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, LLMError> {
+        self.list_models().await
+    }
+}
@@ -0,0 +1,352 @@
+use super::http::{self, HttpClient};
+use super::{
+    sse, usage, GenerationParams, LLMComplete, LLMError, Message, ModelInfo, Role, TextStream,
+};
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+
+/// Finish reasons of candidates withheld by Gemini's content filters
+const BLOCKED_FINISH_REASONS: [&str; 5] = [
+    "SAFETY",
+    "RECITATION",
+    "BLOCKLIST",
+    "PROHIBITED_CONTENT",
+    "SPII",
+];
+
+#[derive(Clone)]
+pub struct GeminiConfig {
+    api_key: String,
+    model: String,
+    api_base_url: String,
+}
+
+impl GeminiConfig {
+    pub fn new(api_key: String, model: Option<String>, base_url: Option<String>) -> Self {
+        let base_url = base_url
+            .unwrap_or_else(|| "https://generativelanguage.googleapis.com/v1beta".to_string());
+
+        Self {
+            api_key,
+            model: model.unwrap_or_else(|| "gemini-2.0-flash".to_string()),
+            api_base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct GeminiProvider {
+    client: HttpClient,
+    config: GeminiConfig,
+}
+
+impl GeminiProvider {
+    pub fn new(client: HttpClient, config: GeminiConfig) -> Self {
+        Self { client, config }
+    }
+
+    fn build_payload(messages: &[Message], params: &GenerationParams) -> Value {
+        let system_instruction = messages
+            .iter()
+            .filter(|message| message.role == Role::System)
+            .map(|message| message.content.as_str())
+            .collect::<Vec<_>>()
+            .join("\n\n");
+
+        // Gemini calls the assistant role `model`
+        let contents = messages
+            .iter()
+            .filter_map(|message| {
+                let role = match message.role {
+                    Role::System => return None,
+                    Role::User => "user",
+                    Role::Assistant => "model",
+                };
+                Some(json!({
+                    "role": role,
+                    "parts": [{ "text": message.content }],
+                }))
+            })
+            .collect::<Vec<Value>>();
+
+        let mut payload = json!({ "contents": contents });
+
+        if !system_instruction.is_empty() {
+            payload["systemInstruction"] = json!({ "parts": [{ "text": system_instruction }] });
+        }
+
+        let mut generation_config = json!({});
+        if let Some(temperature) = params.temperature {
+            generation_config["temperature"] = json!(temperature);
+        }
+        if let Some(top_p) = params.top_p {
+            generation_config["topP"] = json!(top_p);
+        }
+        if let Some(max_tokens) = params.max_tokens {
+            generation_config["maxOutputTokens"] = json!(max_tokens);
+        }
+        if !params.stop.is_empty() {
+            generation_config["stopSequences"] = json!(params.stop);
+        }
+        if let Some(seed) = params.seed {
+            generation_config["seed"] = json!(seed);
+        }
+        if let Some(response_schema) = &params.response_schema {
+            generation_config["responseMimeType"] = json!("application/json");
+            generation_config["responseSchema"] = Self::openapi_schema(&response_schema.schema);
+        }
+        if generation_config != json!({}) {
+            payload["generationConfig"] = generation_config;
+        }
+
+        payload
+    }
+
+    /// Gemini takes an OpenAPI subset of JSON schemas, without `additionalProperties`
+    fn openapi_schema(schema: &Value) -> Value {
+        match schema {
+            Value::Object(object) => object
+                .iter()
+                .filter(|(key, _)| key.as_str() != "additionalProperties")
+                .map(|(key, value)| (key.clone(), Self::openapi_schema(value)))
+                .collect(),
+            Value::Array(items) => items.iter().map(Self::openapi_schema).collect(),
+            value => value.clone(),
+        }
+    }
+
+    /// Maps Google's `{"error": {"code": ..., "message": ..., "status": ...}}` errors
+    fn parse_error(status: StatusCode, error_json: &Value) -> Option<LLMError> {
+        let error = error_json.get("error");
+        let message = error
+            .and_then(|error| error.get("message"))
+            .and_then(|msg| msg.as_str())
+            .unwrap_or_default()
+            .to_string();
+
+        let error = match error?.get("status")?.as_str()? {
+            "UNAUTHENTICATED" | "PERMISSION_DENIED" => LLMError::AuthError(message),
+            "RESOURCE_EXHAUSTED" => LLMError::RateLimited(message),
+            "UNAVAILABLE" => LLMError::Overloaded(message),
+            "INVALID_ARGUMENT" | "NOT_FOUND" | "FAILED_PRECONDITION" => {
+                LLMError::InvalidRequest(message)
+            }
+            _ => http::status_error(status, message),
+        };
+        Some(error)
+    }
+
+    async fn send(
+        &self,
+        method: &str,
+        query: &[(&str, &str)],
+        payload: &Value,
+    ) -> Result<reqwest::Response, LLMError> {
+        let url = format!(
+            "{}/models/{}:{}",
+            self.config.api_base_url, self.config.model, method
+        );
+
+        let response = self
+            .client
+            .send(|client| {
+                client
+                    .post(&url)
+                    .header("x-goog-api-key", &self.config.api_key)
+                    .query(query)
+                    .json(payload)
+            })
+            .await?;
+
+        let status = response.status();
+        match status {
+            StatusCode::OK => Ok(response),
+            _ => Err(http::response_error(response, Self::parse_error).await),
+        }
+    }
+
+    /// The text of a `GenerateContentResponse`, or why the model gave none
+    fn parse_response(response_json: &Value) -> Result<String, LLMError> {
+        if let Some(block_reason) = response_json
+            .get("promptFeedback")
+            .and_then(|feedback| feedback.get("blockReason"))
+            .and_then(|reason| reason.as_str())
+        {
+            return Err(LLMError::SafetyBlocked(format!(
+                "prompt blocked ({})",
+                block_reason
+            )));
+        }
+
+        let candidate = response_json
+            .get("candidates")
+            .and_then(|candidates| candidates.get(0))
+            .ok_or(LLMError::NoCompletionChoice)?;
+
+        let text = candidate
+            .get("content")
+            .and_then(|content| content.get("parts"))
+            .and_then(|parts| parts.as_array())
+            .map(|parts| {
+                parts
+                    .iter()
+                    .filter_map(|part| part.get("text").and_then(|text| text.as_str()))
+                    .collect::<String>()
+            })
+            .unwrap_or_default();
+
+        match candidate
+            .get("finishReason")
+            .and_then(|reason| reason.as_str())
+        {
+            Some(reason) if BLOCKED_FINISH_REASONS.contains(&reason) => Err(
+                LLMError::SafetyBlocked(format!("response blocked ({})", reason)),
+            ),
+            Some("MAX_TOKENS") => Err(LLMError::Incomplete(
+                "the response reached the maximum number of tokens".to_string(),
+            )),
+            _ => Ok(text),
+        }
+    }
+
+    fn record_usage(response_json: &Value, model: &str) {
+        if let Some(metadata) = response_json.get("usageMetadata") {
+            usage::record(
+                model,
+                metadata
+                    .get("promptTokenCount")
+                    .and_then(Value::as_u64)
+                    .unwrap_or_default(),
+                metadata
+                    .get("candidatesTokenCount")
+                    .and_then(Value::as_u64)
+                    .unwrap_or_default(),
+            );
+        }
+    }
+
+    /// Every chunk carries the running usage, only the last one, with a finish reason, is recorded
+    fn parse_event(data: &str, model: &str) -> Option<Result<String, LLMError>> {
+        let json_value: Value = serde_json::from_str(data).ok()?;
+
+        let is_last = json_value
+            .get("candidates")
+            .and_then(|candidates| candidates.get(0))
+            .and_then(|candidate| candidate.get("finishReason"))
+            .is_some();
+        if is_last {
+            Self::record_usage(&json_value, model);
+        }
+
+        match Self::parse_response(&json_value) {
+            Ok(text) if text.is_empty() => None,
+            result => Some(result),
+        }
+    }
+
+    async fn complete(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<String, LLMError> {
+        let response = self
+            .send("generateContent", &[], &Self::build_payload(messages, params))
+            .await?;
+        let response_json: Value = response.json().await?;
+        Self::record_usage(&response_json, &self.config.model);
+
+        let text = Self::parse_response(&response_json)?;
+        if text.is_empty() {
+            return Err(LLMError::NoCompletionChoice);
+        }
+        Ok(text)
+    }
+
+    async fn complete_stream(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<TextStream, LLMError> {
+        let response = self
+            .send(
+                "streamGenerateContent",
+                &[("alt", "sse")],
+                &Self::build_payload(messages, params),
+            )
+            .await?;
+        let model = self.config.model.clone();
+
+        Ok(sse::data_events(response)
+            .filter_map(move |event| {
+                let model = model.clone();
+                async move {
+                    match event {
+                        Ok(data) => Self::parse_event(&data, &model),
+                        Err(e) => Some(Err(e)),
+                    }
+                }
+            })
+            .boxed())
+    }
+
+    /// Only the models that generate content, not the embedding ones
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, LLMError> {
+        let url = format!("{}/models", self.config.api_base_url);
+        let response_json = self
+            .client
+            .get_json(|client| {
+                client
+                    .get(&url)
+                    .header("x-goog-api-key", &self.config.api_key)
+                    .query(&[("pageSize", "1000")])
+            })
+            .await?;
+
+        let models = response_json
+            .get("models")
+            .and_then(|models| models.as_array())
+            .ok_or(LLMError::UnexpectedResponse)?;
+
+        Ok(models
+            .iter()
+            .filter(|model| {
+                model
+                    .get("supportedGenerationMethods")
+                    .and_then(|methods| methods.as_array())
+                    .is_some_and(|methods| methods.contains(&json!("generateContent")))
+            })
+            .filter_map(|model| {
+                let name = model.get("name")?.as_str()?;
+                Some(ModelInfo {
+                    id: name.strip_prefix("models/").unwrap_or(name).to_string(),
+                    context_window: model.get("inputTokenLimit").and_then(Value::as_u64),
+                })
+            })
+            .collect())
+    }
+}
+
+#[async_trait]
+impl LLMComplete for GeminiProvider {
+    async fn complete(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<String, LLMError> {
+        self.complete(messages, params).await
+    }
+
+    async fn complete_stream(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<TextStream, LLMError> {
+        self.complete_stream(messages, params).await
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, LLMError> {
+        self.list_models().await
+    }
+}
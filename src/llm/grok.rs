@@ -1,4 +1,8 @@
-use super::{LLMComplete, LLMError, Message, Role};
+use super::http::{self, HttpClient};
+use super::{
+    openai, sse, usage, GenerationParams, LLMComplete, LLMError, Message, ModelInfo, Role,
+    TextStream,
+};
 use async_trait::async_trait;
 use reqwest::StatusCode;
 use serde_json::{json, Value};
@@ -8,80 +12,175 @@ pub struct GroqConfig {
     api_key: String,
     model: String,
     api_base_url: String,
+    models_url: String,
 }
 
 impl GroqConfig {
-    pub fn new(api_key: String, model: Option<String>) -> Self {
+    pub fn new(api_key: String, model: Option<String>, base_url: Option<String>) -> Self {
+        let base_url = base_url.unwrap_or_else(|| "https://api.groq.com/openai/v1".to_string());
+
+        let base_url = base_url.trim_end_matches('/');
+
         Self {
             api_key,
-            model: model.unwrap_or_else(|| "mixtral-8x7b-32768".to_string()),
-            api_base_url: "https://api.groq.com/openai/v1/chat/completions".to_string(),
+            model: model.unwrap_or_else(|| "llama-3.3-70b-versatile".to_string()),
+            api_base_url: format!("{}/chat/completions", base_url),
+            models_url: format!("{}/models", base_url),
         }
     }
 }
 #[derive(Clone)]
 pub struct GroqProvider {
-    client: reqwest::Client,
+    client: HttpClient,
     config: GroqConfig,
 }
 
 impl GroqProvider {
-    pub fn new(client: reqwest::Client, config: GroqConfig) -> Self {
+    pub fn new(client: HttpClient, config: GroqConfig) -> Self {
         Self { client, config }
     }
 
-    async fn complete(&self, messages: &[Message]) -> Result<String, LLMError> {
-        let payload = json!({
+    fn build_payload(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+        stream: bool,
+    ) -> Value {
+        let mut payload = json!({
             "model": self.config.model,
             "messages": messages.iter().map(|message| {
                 json!({
                     "role": match message.role {
                         Role::System => "system",
                         Role::User => "user",
+                        Role::Assistant => "assistant",
                     },
                     "content": message.content,
                 })
             }).collect::<Vec<Value>>(),
+            "stream": stream,
         });
 
+        if let Some(temperature) = params.temperature {
+            payload["temperature"] = json!(temperature);
+        }
+        if let Some(top_p) = params.top_p {
+            payload["top_p"] = json!(top_p);
+        }
+        if let Some(max_tokens) = params.max_tokens {
+            payload["max_tokens"] = json!(max_tokens);
+        }
+        if !params.stop.is_empty() {
+            payload["stop"] = json!(params.stop);
+        }
+        if let Some(seed) = params.seed {
+            payload["seed"] = json!(seed);
+        }
+        // JSON schemas are limited to a few models, JSON mode works with all of them
+        if params.response_schema.is_some() {
+            payload["response_format"] = json!({ "type": "json_object" });
+        }
+
+        // Without it streams do not report their usage
+        if stream {
+            payload["stream_options"] = json!({ "include_usage": true });
+        }
+
+        payload
+    }
+
+    async fn send(&self, payload: &Value) -> Result<reqwest::Response, LLMError> {
         let response = self
             .client
-            .post(&self.config.api_base_url)
-            .header("Authorization", format!("Bearer {}", self.config.api_key))
-            .json(&payload)
-            .send()
+            .send(|client| {
+                client
+                    .post(&self.config.api_base_url)
+                    .header("Authorization", format!("Bearer {}", self.config.api_key))
+                    .json(payload)
+            })
             .await?;
 
         let status = response.status();
         match status {
-            StatusCode::OK => {
-                let response_json: Value = response.json().await?;
-                let content = response_json
-                    .get("choices")
-                    .and_then(|choices| choices.get(0))
-                    .and_then(|choice| choice.get("message"))
-                    .and_then(|message| message.get("content"))
-                    .and_then(|content| content.as_str())
-                    .ok_or(LLMError::NoCompletionChoice)?;
-                Ok(content.to_string())
-            }
-            _ => {
-                let error_json: Value = response.json().await?;
-                let error_message = error_json
-                    .get("error")
-                    .and_then(|error| error.get("message"))
-                    .and_then(|msg| msg.as_str())
-                    .ok_or(LLMError::UnexpectedResponse)?
-                    .into();
-                Err(LLMError::APIError(status, error_message))
-            }
+            StatusCode::OK => Ok(response),
+            _ => Err(http::response_error(response, http::openai_error).await),
         }
     }
+
+    fn parse_delta(data: &str, model: &str) -> Option<String> {
+        let json_value: Value = serde_json::from_str(data).ok()?;
+        // The usage comes in a last chunk without choices
+        usage::record_openai(&json_value, model);
+        json_value
+            .get("choices")?
+            .get(0)?
+            .get("delta")?
+            .get("content")?
+            .as_str()
+            .map(String::from)
+    }
+
+    async fn complete(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<String, LLMError> {
+        let response = self.send(&self.build_payload(messages, params, false)).await?;
+        let response_json: Value = response.json().await?;
+        usage::record_openai(&response_json, &self.config.model);
+        let content = response_json
+            .get("choices")
+            .and_then(|choices| choices.get(0))
+            .and_then(|choice| choice.get("message"))
+            .and_then(|message| message.get("content"))
+            .and_then(|content| content.as_str())
+            .ok_or(LLMError::NoCompletionChoice)?;
+        Ok(content.to_string())
+    }
+
+    async fn complete_stream(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<TextStream, LLMError> {
+        let response = self.send(&self.build_payload(messages, params, true)).await?;
+        let model = self.config.model.clone();
+
+        Ok(sse::text_deltas(response, move |data| Self::parse_delta(data, &model)))
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, LLMError> {
+        let response_json = self
+            .client
+            .get_json(|client| {
+                client
+                    .get(&self.config.models_url)
+                    .header("Authorization", format!("Bearer {}", self.config.api_key))
+            })
+            .await?;
+        openai::parse_models(&response_json)
+    }
 }
 
 #[async_trait]
 impl LLMComplete for GroqProvider {
-    async fn complete(&self, messages: &[Message]) -> Result<String, LLMError> {
-        self.complete(&messages).await
+    async fn complete(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<String, LLMError> {
+        self.complete(messages, params).await
+    }
+
+    async fn complete_stream(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<TextStream, LLMError> {
+        self.complete_stream(messages, params).await
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, LLMError> {
+        self.list_models().await
     }
 }
@@ -0,0 +1,385 @@
+use super::LLMError;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::{RequestBuilder, Response, StatusCode};
+use serde_json::Value;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Longest `Retry-After` worth waiting for, past it the rate limit counts as exhausted
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Debug)]
+pub struct RequestPolicy {
+    pub connect_timeout: Duration,
+    /// Longest wait for the next chunk of a response, so long streams are not cut off
+    pub read_timeout: Duration,
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RequestPolicy {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(10),
+            read_timeout: Duration::from_secs(120),
+            max_attempts: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RequestPolicy {
+    /// Exponential backoff with equal jitter: half the delay is fixed, half is random
+    fn backoff(&self, attempt: u32) -> Duration {
+        let delay = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt - 1))
+            .min(self.max_delay);
+
+        // Nanoseconds of the clock are random enough to spread out concurrent clients
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.subsec_nanos())
+            .unwrap_or_default();
+        let jitter = delay / 2 * (nanos % 1000) / 1000;
+
+        delay / 2 + jitter
+    }
+}
+
+/// The client shared by all providers, which applies the [`RequestPolicy`] to every request
+#[derive(Clone)]
+pub struct HttpClient {
+    client: reqwest::Client,
+    policy: RequestPolicy,
+    /// Extra headers of every request, set by the user for one provider
+    headers: HeaderMap,
+}
+
+impl HttpClient {
+    pub fn new(client: reqwest::Client, policy: RequestPolicy) -> Self {
+        Self {
+            client,
+            policy,
+            headers: HeaderMap::new(),
+        }
+    }
+
+    /// The same client, sending `headers` with every request instead of its own extra headers
+    pub fn with_headers(&self, headers: HeaderMap) -> Self {
+        Self {
+            headers,
+            ..self.clone()
+        }
+    }
+
+    /// Adds the extra headers the provider did not set itself, with all of their values
+    async fn execute(&self, request: RequestBuilder) -> reqwest::Result<Response> {
+        let mut request = request.build()?;
+        for name in self.headers.keys() {
+            if !request.headers().contains_key(name) {
+                for value in self.headers.get_all(name) {
+                    request.headers_mut().append(name.clone(), value.clone());
+                }
+            }
+        }
+        self.client.execute(request).await
+    }
+
+    /// Sends the request built by `build`, retrying on 429, 5xx, connection errors and timeouts.
+    ///
+    /// The last response is returned whatever its status, so providers can parse their own
+    /// error bodies, except for a rate limit that asks to wait longer than we are willing to.
+    pub async fn send<F>(&self, build: F) -> Result<Response, LLMError>
+    where
+        F: Fn(&reqwest::Client) -> RequestBuilder,
+    {
+        let mut attempt = 1;
+        loop {
+            let is_last_attempt = attempt >= self.policy.max_attempts;
+
+            let delay = match self.execute(build(&self.client)).await {
+                Ok(response) => {
+                    let status = response.status();
+                    if !is_retryable(status) || is_last_attempt {
+                        return Ok(response);
+                    }
+
+                    match retry_after(&response) {
+                        Some(wait) if wait > MAX_RETRY_AFTER => {
+                            return Err(LLMError::RateLimited(format!(
+                                "the provider asked to wait {} seconds before retrying",
+                                wait.as_secs()
+                            )))
+                        }
+                        Some(wait) => wait,
+                        None => self.policy.backoff(attempt),
+                    }
+                }
+                Err(e) if (e.is_connect() || e.is_timeout()) && !is_last_attempt => {
+                    self.policy.backoff(attempt)
+                }
+                Err(e) => return Err(transport_error(e)),
+            };
+
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Sends a request for a JSON document, such as a model list, whose errors are told apart
+    /// by their status alone
+    pub async fn get_json<F>(&self, build: F) -> Result<Value, LLMError>
+    where
+        F: Fn(&reqwest::Client) -> RequestBuilder,
+    {
+        let response = self.send(build).await?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(status_error(status, response.text().await?));
+        }
+        Ok(response.json().await?)
+    }
+}
+
+fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+fn retry_after(response: &Response) -> Option<Duration> {
+    let value = response.headers().get(RETRY_AFTER)?.to_str().ok()?;
+    parse_retry_after(value, SystemTime::now())
+}
+
+/// Either delay-seconds or an HTTP date, which counts from `now` and is no wait once passed
+fn parse_retry_after(value: &str, now: SystemTime) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = UNIX_EPOCH + Duration::from_secs(parse_http_date(value)?);
+    Some(date.duration_since(now).unwrap_or_default())
+}
+
+/// Seconds since the epoch of an IMF-fixdate, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`, the only
+/// format servers are allowed to send
+fn parse_http_date(value: &str) -> Option<u64> {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let (_weekday, rest) = value.split_once(", ")?;
+    let parts: Vec<&str> = rest.split(' ').collect();
+    let [day, month, year, time, "GMT"] = parts[..] else {
+        return None;
+    };
+    let day: u64 = day.parse().ok()?;
+    let month = MONTHS.iter().position(|name| *name == month)? as u64 + 1;
+    let year: u64 = year.parse().ok()?;
+    let time: Vec<u64> = time
+        .split(':')
+        .map(|part| part.parse().ok())
+        .collect::<Option<_>>()?;
+    let [hours, minutes, seconds] = time[..] else {
+        return None;
+    };
+    if year < 1970 || !(1..=31).contains(&day) || hours > 23 || minutes > 59 || seconds > 60 {
+        return None;
+    }
+
+    // Days from the civil date, counting years from March so the leap day comes last
+    let (year, month) = if month <= 2 {
+        (year - 1, month + 9)
+    } else {
+        (year, month - 3)
+    };
+    let era = year / 400;
+    let year_of_era = year % 400;
+    let day_of_year = (153 * month + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    Some(days * 86_400 + hours * 3_600 + minutes * 60 + seconds)
+}
+
+/// Tells timeouts apart from the other transport errors
+pub fn transport_error(error: reqwest::Error) -> LLMError {
+    if error.is_timeout() {
+        LLMError::Timeout(error.to_string())
+    } else {
+        LLMError::RequestError(error)
+    }
+}
+
+/// The error of a failed response: the one `parse` finds in a JSON body, or else one told apart
+/// by the status alone, e.g. for the HTML page of a proxy
+pub async fn response_error<F>(response: Response, parse: F) -> LLMError
+where
+    F: FnOnce(StatusCode, &Value) -> Option<LLMError>,
+{
+    let status = response.status();
+    let body = match response.text().await {
+        Ok(body) => body,
+        Err(e) => return transport_error(e),
+    };
+
+    serde_json::from_str::<Value>(&body)
+        .ok()
+        .and_then(|json| parse(status, &json))
+        .unwrap_or_else(|| {
+            let body = body.trim();
+            let message = if body.is_empty() {
+                status.canonical_reason().unwrap_or_default()
+            } else {
+                body
+            };
+            status_error(status, message.to_string())
+        })
+}
+
+/// The message of an OpenAI style error, `{"error":{"message":…}}`, also found as
+/// `{"error":"…"}` (Ollama) and `{"object":"error","message":…}` (vLLM)
+pub fn error_message(error_json: &Value) -> Option<&str> {
+    match error_json.get("error") {
+        Some(Value::String(message)) => Some(message),
+        Some(error) => error.get("message")?.as_str(),
+        None => error_json.get("message")?.as_str(),
+    }
+}
+
+/// The error of a failed OpenAI compatible response
+pub fn openai_error(status: StatusCode, error_json: &Value) -> Option<LLMError> {
+    Some(status_error(status, error_message(error_json)?.to_string()))
+}
+
+/// The error for a failed response whose body has no more specific error type
+pub fn status_error(status: StatusCode, message: String) -> LLMError {
+    match status {
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => LLMError::AuthError(message),
+        StatusCode::TOO_MANY_REQUESTS => LLMError::RateLimited(message),
+        StatusCode::REQUEST_TIMEOUT | StatusCode::GATEWAY_TIMEOUT => LLMError::Timeout(message),
+        _ => LLMError::APIError(status, message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn backs_off_exponentially_up_to_the_max_delay() {
+        let policy = RequestPolicy {
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(500),
+            ..Default::default()
+        };
+
+        for (attempt, full_delay) in [(1, 100), (2, 200), (3, 400), (4, 500), (30, 500)] {
+            let delay = policy.backoff(attempt);
+            let full_delay = Duration::from_millis(full_delay);
+            assert!(
+                delay >= full_delay / 2 && delay <= full_delay,
+                "{:?}",
+                delay
+            );
+        }
+    }
+
+    #[test]
+    fn parses_retry_after_seconds_and_dates() {
+        let now = UNIX_EPOCH + Duration::from_secs(784_111_777);
+
+        assert_eq!(
+            parse_retry_after(" 120 ", now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            parse_retry_after("Sun, 06 Nov 1994 08:50:07 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_retry_after("Sun, 06 Nov 1994 08:49:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(
+            parse_retry_after("Sunday, 06-Nov-94 08:49:37 GMT", now),
+            None
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+
+    #[test]
+    fn parses_http_dates() {
+        assert_eq!(parse_http_date("Thu, 01 Jan 1970 00:00:00 GMT"), Some(0));
+        assert_eq!(
+            parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"),
+            Some(784_111_777)
+        );
+        assert_eq!(
+            parse_http_date("Thu, 29 Feb 2024 12:00:00 GMT"),
+            Some(1_709_208_000)
+        );
+        assert_eq!(parse_http_date("Thu, 29 Feb 2024 12:00:00 UTC"), None);
+        assert_eq!(parse_http_date("Thu, 29 Foo 2024 12:00:00 GMT"), None);
+    }
+
+    #[test]
+    fn reads_error_messages_of_openai_compatible_servers() {
+        let message =
+            |body: &str| error_message(&serde_json::from_str(body).unwrap()).map(String::from);
+
+        assert_eq!(
+            message(r#"{"error":{"message":"bad key"}}"#).unwrap(),
+            "bad key"
+        );
+        assert_eq!(
+            message(r#"{"error":"model not found"}"#).unwrap(),
+            "model not found"
+        );
+        assert_eq!(
+            message(r#"{"object":"error","message":"too long"}"#).unwrap(),
+            "too long"
+        );
+        assert_eq!(message(r#"{"detail":"nope"}"#), None);
+    }
+
+    #[tokio::test]
+    async fn retries_timeouts() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let connections = Arc::new(AtomicUsize::new(0));
+        // Accepts every connection and never answers
+        let server = tokio::spawn({
+            let connections = connections.clone();
+            async move {
+                let mut sockets = Vec::new();
+                loop {
+                    sockets.push(listener.accept().await.unwrap());
+                    connections.fetch_add(1, Ordering::SeqCst);
+                }
+            }
+        });
+
+        let client = HttpClient::new(
+            reqwest::Client::builder()
+                .timeout(Duration::from_millis(200))
+                .build()
+                .unwrap(),
+            RequestPolicy {
+                max_attempts: 2,
+                base_delay: Duration::from_millis(1),
+                ..Default::default()
+            },
+        );
+        let response = client.send(|client| client.get(&url)).await;
+
+        server.abort();
+        assert!(matches!(response, Err(LLMError::Timeout(_))));
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
+    }
+}
@@ -1,27 +1,178 @@
-use crate::{cli::LLMProviderType, error::GitAIError};
+use crate::{cli::LLMProviderType, config::GitAIConfig, error::GitAIError};
 use anthropic::AnthropicProvider;
 use async_trait::async_trait;
-use openai::OpenAIProvider;
+use azure::{AzureAuth, AzureProvider};
+use cache::{CachedProvider, ResponseCache};
+use clap::ValueEnum;
+use exec::ExecProvider;
+use fallback::FallbackChain;
+use futures_util::stream::BoxStream;
+use gemini::GeminiProvider;
+use http::{HttpClient, RequestPolicy};
+use ollama::OllamaProvider;
+use openai::{AuthScheme, OpenAIProvider};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
 use phind::PhindProvider;
 use grok::GroqProvider;
+use replay::{RecordingProvider, ReplayProvider};
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
 use thiserror::Error;
 
 pub mod anthropic;
+pub mod azure;
+pub mod cache;
+pub mod exec;
+pub mod fallback;
+pub mod gemini;
+pub mod http;
+pub mod ollama;
 pub mod openai;
 pub mod phind;
 pub mod grok;
+pub mod replay;
+mod sse;
+#[cfg(test)]
+mod stand_in;
+pub mod usage;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Role {
     System,
     User,
+    Assistant,
 }
 
+#[derive(Serialize)]
 pub struct Message {
     pub role: Role,
     pub content: String,
 }
 
+/// A model offered by a provider
+#[derive(Clone, Debug)]
+pub struct ModelInfo {
+    pub id: String,
+    /// In tokens, for the providers that report it
+    pub context_window: Option<u64>,
+}
+
+/// Whether two model names are the same model. Ollama lists `llama3.2` as `llama3.2:latest`,
+/// a name without a tag stands for that one
+pub fn same_model(a: &str, b: &str) -> bool {
+    let with_tag = |name: &str| match name.rsplit_once(':') {
+        // A colon inside a path or before a registry port is not a tag
+        Some((_, tag)) if !tag.contains('/') => name.to_string(),
+        _ => format!("{}:latest", name),
+    };
+    with_tag(a) == with_tag(b)
+}
+
+/// A JSON schema the response must follow, sent to providers with native structured output
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ResponseSchema {
+    pub name: String,
+    pub schema: serde_json::Value,
+}
+
+/// Sampling settings of a request, the unset ones are left to the provider's defaults
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct GenerationParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub stop: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    /// Set by commands that parse the response, never from the config
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_schema: Option<ResponseSchema>,
+    /// Parameters filled from a command's built-in defaults, ignored without a warning
+    #[serde(skip)]
+    pub builtin: Vec<&'static str>,
+}
+
+/// Providers already warned about, so that a command sending several requests warns once
+static UNSUPPORTED_WARNINGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+impl GenerationParams {
+    /// Fills the unset parameters from `defaults`
+    pub fn or(self, defaults: &GenerationParams) -> Self {
+        Self {
+            temperature: self.temperature.or(defaults.temperature),
+            top_p: self.top_p.or(defaults.top_p),
+            max_tokens: self.max_tokens.or(defaults.max_tokens),
+            stop: if self.stop.is_empty() {
+                defaults.stop.clone()
+            } else {
+                self.stop
+            },
+            seed: self.seed.or(defaults.seed),
+            response_schema: self.response_schema.or(defaults.response_schema.clone()),
+            builtin: self.builtin,
+        }
+    }
+
+    /// Fills the unset parameters from a command's built-in `defaults`, which providers may
+    /// ignore quietly since nobody asked for them
+    pub fn or_builtin(self, defaults: &GenerationParams) -> Self {
+        let configured = self.set_names();
+        let params = self.or(defaults);
+        let builtin = params
+            .set_names()
+            .into_iter()
+            .filter(|name| !configured.contains(name))
+            .collect();
+
+        Self { builtin, ..params }
+    }
+
+    fn set_names(&self) -> Vec<&'static str> {
+        [
+            ("temperature", self.temperature.is_some()),
+            ("top_p", self.top_p.is_some()),
+            ("max_tokens", self.max_tokens.is_some()),
+            ("stop", !self.stop.is_empty()),
+            ("seed", self.seed.is_some()),
+        ]
+        .into_iter()
+        .filter_map(|(name, set)| set.then_some(name))
+        .collect()
+    }
+
+    /// Warns about the set parameters the provider has no request field for, they are not sent
+    fn warn_unsupported(&self, provider: &str, supported: &[&str]) {
+        let ignored = self
+            .set_names()
+            .into_iter()
+            .filter(|name| !supported.contains(name) && !self.builtin.contains(name))
+            .collect::<Vec<_>>();
+        if ignored.is_empty() {
+            return;
+        }
+
+        let mut warned = UNSUPPORTED_WARNINGS.lock().unwrap_or_else(|e| e.into_inner());
+        if warned.iter().any(|name| name == provider) {
+            return;
+        }
+        warned.push(provider.to_string());
+        // Clears the spinner line the command may have started
+        eprintln!(
+            "\r\x1b[2K{} does not support {}, ignoring it",
+            provider,
+            ignored.join(", ")
+        );
+    }
+}
+
 #[derive(Error, Debug)]
 #[error("{0}")]
 pub struct AIPromptError(pub String);
@@ -43,6 +194,37 @@ pub enum LLMError {
     #[error("Unexpected response")]
     UnexpectedResponse,
 
+    #[error("Authentication failed: {0}")]
+    AuthError(String),
+
+    #[error("Rate limited: {0}")]
+    RateLimited(String),
+
+    #[error("Request timed out: {0}")]
+    Timeout(String),
+
+    #[error("Provider is overloaded: {0}")]
+    Overloaded(String),
+
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+
+    #[error("Incomplete response: {0}")]
+    Incomplete(String),
+
+    #[error("Blocked by the provider's safety filters: {0}")]
+    SafetyBlocked(String),
+
+    #[error("Not supported: {0}")]
+    Unsupported(String),
+
+    #[error("Command failed: {0}")]
+    CommandFailed(String),
+
+    /// Stands for the response of a dry run, which ends the command without an error
+    #[error("Dry run, no request was sent")]
+    DryRun,
+
     #[error("Some error occurred")]
     SomeError,
 }
@@ -53,9 +235,29 @@ impl From<GitAIError> for LLMError {
     }
 }
 
+/// Text deltas of a completion, in the order the model produces them
+pub type TextStream = BoxStream<'static, Result<String, LLMError>>;
+
 #[async_trait]
 pub trait LLMComplete: Sync + Send + Clone {
-    async fn complete(&self, messages: &[Message]) -> Result<String, LLMError>;
+    async fn complete(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<String, LLMError>;
+
+    async fn complete_stream(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<TextStream, LLMError>;
+
+    /// The models the provider offers, from its model listing endpoint
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, LLMError> {
+        Err(LLMError::Unsupported(
+            "this provider does not list its models".to_string(),
+        ))
+    }
 }
 
 #[derive(Clone)]
@@ -64,51 +266,528 @@ pub enum LLMProvider {
     Anthropic(AnthropicProvider),
     Phind(PhindProvider),
     Grok(GroqProvider),
+    Ollama(OllamaProvider),
+    Gemini(GeminiProvider),
+    Azure(AzureProvider),
+    Exec(ExecProvider),
+    Fallback(FallbackChain),
+    Cached(CachedProvider),
+    Replay(ReplayProvider),
+    Recording(RecordingProvider),
+    /// Prints the messages instead of sending them, with the model used to estimate the cost
+    DryRun(Option<String>),
+    /// Given to the commands that run without a model, so that they work whatever the config
+    Local,
 }
 
 #[async_trait]
 impl LLMComplete for LLMProvider {
-    async fn complete(&self, messages: &[Message]) -> Result<String, LLMError> {
+    async fn complete(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<String, LLMError> {
+        match self {
+            LLMProvider::Openai(provider) => provider.complete(messages, params).await,
+            LLMProvider::Anthropic(provider) => provider.complete(messages, params).await,
+            LLMProvider::Phind(provider) => provider.complete(messages, params).await,
+            LLMProvider::Grok(provider) => provider.complete(messages, params).await,
+            LLMProvider::Ollama(provider) => provider.complete(messages, params).await,
+            LLMProvider::Gemini(provider) => provider.complete(messages, params).await,
+            LLMProvider::Azure(provider) => provider.complete(messages, params).await,
+            LLMProvider::Exec(provider) => provider.complete(messages, params).await,
+            LLMProvider::Fallback(chain) => chain.complete(messages, params).await,
+            LLMProvider::Cached(provider) => provider.complete(messages, params).await,
+            LLMProvider::Replay(provider) => provider.complete(messages, params).await,
+            LLMProvider::Recording(provider) => provider.complete(messages, params).await,
+            LLMProvider::DryRun(model) => dry_run(messages, params, model.as_deref()),
+            LLMProvider::Local => Err(no_provider()),
+        }
+    }
+
+    async fn complete_stream(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<TextStream, LLMError> {
         match self {
-            LLMProvider::Openai(provider) => provider.complete(messages).await,
-            LLMProvider::Anthropic(provider) => provider.complete(messages).await,
-            LLMProvider::Phind(provider) => provider.complete(messages).await,
-            LLMProvider::Grok(provider) => provider.complete(messages).await,
+            LLMProvider::Openai(provider) => provider.complete_stream(messages, params).await,
+            LLMProvider::Anthropic(provider) => provider.complete_stream(messages, params).await,
+            LLMProvider::Phind(provider) => provider.complete_stream(messages, params).await,
+            LLMProvider::Grok(provider) => provider.complete_stream(messages, params).await,
+            LLMProvider::Ollama(provider) => provider.complete_stream(messages, params).await,
+            LLMProvider::Gemini(provider) => provider.complete_stream(messages, params).await,
+            LLMProvider::Azure(provider) => provider.complete_stream(messages, params).await,
+            LLMProvider::Exec(provider) => provider.complete_stream(messages, params).await,
+            LLMProvider::Fallback(chain) => chain.complete_stream(messages, params).await,
+            LLMProvider::Cached(provider) => provider.complete_stream(messages, params).await,
+            LLMProvider::Replay(provider) => provider.complete_stream(messages, params).await,
+            LLMProvider::Recording(provider) => provider.complete_stream(messages, params).await,
+            LLMProvider::DryRun(model) => dry_run(messages, params, model.as_deref()),
+            LLMProvider::Local => Err(no_provider()),
+        }
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, LLMError> {
+        match self {
+            LLMProvider::Openai(provider) => provider.list_models().await,
+            LLMProvider::Anthropic(provider) => provider.list_models().await,
+            LLMProvider::Phind(provider) => provider.list_models().await,
+            LLMProvider::Grok(provider) => provider.list_models().await,
+            LLMProvider::Ollama(provider) => provider.list_models().await,
+            LLMProvider::Gemini(provider) => provider.list_models().await,
+            LLMProvider::Azure(provider) => provider.list_models().await,
+            LLMProvider::Exec(provider) => provider.list_models().await,
+            LLMProvider::Fallback(chain) => chain.list_models().await,
+            LLMProvider::Cached(provider) => provider.list_models().await,
+            LLMProvider::Replay(provider) => provider.list_models().await,
+            LLMProvider::Recording(provider) => provider.list_models().await,
+            LLMProvider::DryRun(_) => Err(LLMError::Unsupported(
+                "a dry run does not list models".to_string(),
+            )),
+            LLMProvider::Local => Err(no_provider()),
+        }
+    }
+}
+
+fn read_pem(path: &Path, what: &str) -> Result<Vec<u8>, GitAIError> {
+    fs::read(path).map_err(|e| {
+        GitAIError::ConfigError(format!("Could not read the {} {}: {}", what, path.display(), e))
+    })
+}
+
+/// Applies the proxy, the extra root certificates and the client certificate
+fn configure_network(
+    mut builder: reqwest::ClientBuilder,
+    gitai_config: &GitAIConfig,
+) -> Result<reqwest::ClientBuilder, GitAIError> {
+    // An explicit proxy replaces the one from the environment
+    if let Some(proxy_url) = &gitai_config.proxy {
+        let proxy = reqwest::Proxy::all(proxy_url)
+            .map_err(|e| GitAIError::ConfigError(format!("Invalid proxy '{}': {}", proxy_url, e)))?
+            .no_proxy(
+                gitai_config
+                    .no_proxy
+                    .as_deref()
+                    .and_then(reqwest::NoProxy::from_string),
+            );
+        builder = builder.proxy(proxy);
+    }
+
+    for path in &gitai_config.ca_certs {
+        let certificates = reqwest::Certificate::from_pem_bundle(&read_pem(path, "CA certificate")?)
+            .map_err(|e| {
+                GitAIError::ConfigError(format!("Invalid CA certificate {}: {}", path.display(), e))
+            })?;
+        for certificate in certificates {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+
+    match (&gitai_config.client_cert, &gitai_config.client_key) {
+        (Some(cert), Some(key)) => {
+            let identity = reqwest::Identity::from_pkcs8_pem(
+                &read_pem(cert, "client certificate")?,
+                &read_pem(key, "client key")?,
+            )
+            .map_err(|e| GitAIError::ConfigError(format!("Invalid client certificate: {}", e)))?;
+            builder = builder.identity(identity);
+        }
+        (None, None) => {}
+        _ => {
+            return Err(GitAIError::ConfigError(
+                "A client certificate needs both CLIENT_CERT and CLIENT_KEY".to_string(),
+            ))
         }
     }
+
+    Ok(builder)
+}
+
+/// A client that sends the configured extra headers with every request, with the configured
+/// timeouts, retries, proxy and certificates
+fn build_client(gitai_config: &GitAIConfig) -> Result<HttpClient, GitAIError> {
+    let default_policy = RequestPolicy::default();
+    let policy = RequestPolicy {
+        connect_timeout: gitai_config
+            .connect_timeout
+            .map_or(default_policy.connect_timeout, Duration::from_secs),
+        read_timeout: gitai_config
+            .read_timeout
+            .map_or(default_policy.read_timeout, Duration::from_secs),
+        max_attempts: gitai_config
+            .max_attempts
+            .unwrap_or(default_policy.max_attempts)
+            .max(1),
+        ..default_policy
+    };
+
+    let builder = reqwest::Client::builder()
+        .connect_timeout(policy.connect_timeout)
+        .read_timeout(policy.read_timeout);
+    let client = configure_network(builder, gitai_config)?
+        .build()
+        .map_err(|e| GitAIError::ConfigError(format!("Could not create HTTP client: {}", e)))?;
+
+    Ok(HttpClient::new(client, policy).with_headers(build_headers(&gitai_config.headers)?))
+}
+
+/// The extra headers of every request; repeated ones are all sent, like repeated `-H` of curl
+fn build_headers(headers: &[(String, String)]) -> Result<HeaderMap, GitAIError> {
+    let mut header_map = HeaderMap::new();
+    for (name, value) in headers {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| GitAIError::ConfigError(format!("Invalid header name '{}'", name)))?;
+        let value = HeaderValue::from_str(value)
+            .map_err(|_| GitAIError::ConfigError(format!("Invalid value for header '{}'", name)))?;
+        header_map.append(name, value);
+    }
+    Ok(header_map)
 }
 
-pub fn get_llm(
-    provider: LLMProviderType,
-    model: Option<String>,
-    api_key: Option<String>,
+fn get_provider(
+    gitai_config: &GitAIConfig,
+    client: HttpClient,
 ) -> Result<LLMProvider, GitAIError> {
-    match provider {
+    let model = gitai_config.model.clone();
+    let api_key = gitai_config.api_key.clone();
+    let base_url = gitai_config.base_url.clone();
+
+    let auth_scheme = match &gitai_config.auth_scheme {
+        Some(auth_scheme) => auth_scheme.parse().map_err(GitAIError::ConfigError)?,
+        None => AuthScheme::Bearer,
+    };
+
+    match gitai_config.provider {
         LLMProviderType::Openai => {
             let api_key = api_key.ok_or(GitAIError::MissingApiKey("OpenAI".to_string()))?;
-            let config = openai::OpenAIConfig::new(api_key, model);
-            let client = reqwest::Client::new();
+            let config = openai::OpenAIConfig::new(Some(api_key), model, base_url, auth_scheme);
+            Ok(LLMProvider::Openai(OpenAIProvider::new(client, config)))
+        }
+        LLMProviderType::OpenaiCompatible => {
+            let base_url = base_url.ok_or(GitAIError::ConfigError(
+                "The openai-compatible provider needs a base URL, use --base-url or BASE_URL"
+                    .to_string(),
+            ))?;
+            let config = openai::OpenAIConfig::new(api_key, model, Some(base_url), auth_scheme);
             Ok(LLMProvider::Openai(OpenAIProvider::new(client, config)))
         }
         LLMProviderType::Anthropic => {
             let api_key = api_key.ok_or(GitAIError::MissingApiKey("Anthropic".to_string()))?;
-            let config = anthropic::AnthropicConfig::new(api_key, model);
-            let client = reqwest::Client::new();
+            let config = anthropic::AnthropicConfig::new(api_key, model, base_url);
             Ok(LLMProvider::Anthropic(AnthropicProvider::new(
                 client, config,
             )))
         }
         LLMProviderType::Phind => {
-            let config = phind::PhindConfig::new(model);
-            let client = reqwest::Client::new();
+            let config = phind::PhindConfig::new(model, base_url);
             Ok(LLMProvider::Phind(PhindProvider::new(client, config)))
         }
         LLMProviderType::Grok => {
             let api_key = api_key.ok_or(GitAIError::MissingApiKey
             ("Grok".to_string()))?;
-            let config = grok::GroqConfig::new(api_key, model);
-            let client = reqwest::Client::new();
+            let config = grok::GroqConfig::new(api_key, model, base_url);
             Ok(LLMProvider::Grok(GroqProvider::new(client, config)))
         }
+        LLMProviderType::Ollama => {
+            let config = ollama::OllamaConfig::new(
+                gitai_config.ollama_host.clone(),
+                gitai_config.ollama_port,
+                model,
+                base_url,
+            );
+            Ok(LLMProvider::Ollama(OllamaProvider::new(client, config)))
+        }
+        LLMProviderType::Gemini => {
+            let api_key = api_key.ok_or(GitAIError::MissingApiKey("Gemini".to_string()))?;
+            let config = gemini::GeminiConfig::new(api_key, model, base_url);
+            Ok(LLMProvider::Gemini(GeminiProvider::new(client, config)))
+        }
+        LLMProviderType::Azure => {
+            // A token command wins over the key, it is how Entra ID only resources are reached
+            let auth = match (&gitai_config.azure_token_command, api_key) {
+                (Some(command), _) => AzureAuth::TokenCommand(command.clone()),
+                (None, Some(api_key)) => AzureAuth::ApiKey(api_key),
+                (None, None) => return Err(GitAIError::MissingApiKey("Azure OpenAI".to_string())),
+            };
+            let endpoint = gitai_config.azure_endpoint.clone().or(base_url).ok_or(
+                GitAIError::ConfigError(
+                    "Azure OpenAI needs the resource endpoint, set AZURE_ENDPOINT".to_string(),
+                ),
+            )?;
+            // Deployments usually carry the model name, so MODEL works as a fallback
+            let deployment = gitai_config.azure_deployment.clone().or(model).ok_or(
+                GitAIError::ConfigError(
+                    "Azure OpenAI needs a deployment name, set AZURE_DEPLOYMENT".to_string(),
+                ),
+            )?;
+            let config = azure::AzureConfig::new(
+                auth,
+                endpoint,
+                deployment,
+                gitai_config.azure_api_version.clone(),
+            );
+            Ok(LLMProvider::Azure(AzureProvider::new(client, config)))
+        }
+        LLMProviderType::Replay => {
+            let path = gitai_config.replay_file.clone().ok_or(GitAIError::ConfigError(
+                "The replay provider needs a fixture, use --replay or REPLAY_FILE".to_string(),
+            ))?;
+            let provider = ReplayProvider::new(path).map_err(GitAIError::ConfigError)?;
+            Ok(LLMProvider::Replay(provider))
+        }
+        LLMProviderType::Exec => {
+            let command = gitai_config.exec_command.clone().ok_or(GitAIError::ConfigError(
+                "The exec provider needs a command, use --exec or EXEC_COMMAND".to_string(),
+            ))?;
+            let config = exec::ExecConfig::new(command, model);
+            Ok(LLMProvider::Exec(ExecProvider::new(config)))
+        }
+    }
+}
+
+fn provider_name(provider: LLMProviderType, model: Option<&str>) -> String {
+    let provider = provider
+        .to_possible_value()
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_default();
+
+    match model {
+        Some(model) => format!("{} ({})", provider, model),
+        None => provider,
+    }
+}
+
+pub fn get_cache(gitai_config: &GitAIConfig) -> Result<ResponseCache, GitAIError> {
+    Ok(ResponseCache::new(
+        GitAIConfig::get_config_dir()?.join("cache"),
+        gitai_config
+            .cache_ttl_days
+            .map_or(cache::DEFAULT_TTL, |days| {
+                Duration::from_secs(days * 24 * 60 * 60)
+            }),
+        gitai_config
+            .cache_max_size_mb
+            .map_or(cache::DEFAULT_MAX_BYTES, |megabytes| megabytes * 1024 * 1024),
+    ))
+}
+
+fn no_provider() -> LLMError {
+    LLMError::Unsupported("this command runs without a provider".to_string())
+}
+
+/// Stops at the first request, commands must not act on a response that was never generated
+fn dry_run<T>(
+    messages: &[Message],
+    params: &GenerationParams,
+    model: Option<&str>,
+) -> Result<T, LLMError> {
+    // Clears the spinner line the command may have started
+    print!("\r\x1b[2K");
+    usage::print_dry_run(messages, params, model);
+    Err(LLMError::DryRun)
+}
+
+/// `cache` serves repeated prompts from the response cache, unless `--no-cache` is given
+pub fn get_llm(gitai_config: &GitAIConfig, cache: bool) -> Result<LLMProvider, GitAIError> {
+    if gitai_config.dry_run {
+        // The cost is estimated for the model the provider would use
+        let model = gitai_config.model.clone().or_else(|| {
+            Some(gitai_config.provider.default_model().to_string())
+                .filter(|model| !model.is_empty())
+        });
+        return Ok(LLMProvider::DryRun(model));
+    }
+
+    let mut llm = get_fallback_chain(gitai_config)?;
+
+    // Replayed responses are already on disk
+    if cache && !gitai_config.no_cache && gitai_config.provider != LLMProviderType::Replay {
+        // The answer of the primary provider stands for the whole chain, a command is to the
+        // exec provider what the base URL is to the others
+        let location = match gitai_config.provider {
+            LLMProviderType::Exec => gitai_config.exec_command.as_deref(),
+            _ => gitai_config.base_url.as_deref(),
+        };
+        let identity = format!(
+            "{}|{}",
+            provider_name(gitai_config.provider, gitai_config.model.as_deref()),
+            location.unwrap_or_default()
+        );
+        llm = LLMProvider::Cached(CachedProvider::new(llm, get_cache(gitai_config)?, identity));
+    }
+
+    // Records what the command got, wherever it came from
+    if let Some(path) = &gitai_config.record_file {
+        llm = LLMProvider::Recording(RecordingProvider::new(llm, path.clone()));
+    }
+
+    Ok(llm)
+}
+
+fn get_fallback_chain(gitai_config: &GitAIConfig) -> Result<LLMProvider, GitAIError> {
+    // One client for every provider of the chain, they share the network settings
+    let client = build_client(gitai_config)?;
+    let primary = get_provider(gitai_config, client.clone())?;
+
+    if gitai_config.fallback.is_empty() {
+        return Ok(primary);
+    }
+
+    let mut providers = vec![(
+        provider_name(gitai_config.provider, gitai_config.model.as_deref()),
+        primary,
+    )];
+
+    for (provider, model) in &gitai_config.fallback {
+        // The base URL, auth scheme and extra headers are settings of the primary provider,
+        // gateway credentials must not reach another provider's API
+        let same_provider = *provider == gitai_config.provider;
+        let fallback_config = GitAIConfig {
+            provider: *provider,
+            model: model.clone(),
+            api_key: gitai_config.api_keys.get(provider).cloned(),
+            base_url: gitai_config.base_url.clone().filter(|_| same_provider),
+            auth_scheme: gitai_config.auth_scheme.clone().filter(|_| same_provider),
+            headers: if same_provider {
+                gitai_config.headers.clone()
+            } else {
+                Vec::new()
+            },
+            fallback: Vec::new(),
+            ..gitai_config.clone()
+        };
+        let client = client.with_headers(build_headers(&fallback_config.headers)?);
+
+        providers.push((
+            provider_name(*provider, model.as_deref()),
+            get_provider(&fallback_config, client)?,
+        ));
+    }
+
+    Ok(LLMProvider::Fallback(FallbackChain::new(providers)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The error `configure_network` gives for `config`, which must fail
+    fn network_error(config: GitAIConfig) -> String {
+        match configure_network(reqwest::Client::builder(), &config) {
+            Err(GitAIError::ConfigError(message)) => message,
+            Err(e) => panic!("not a configuration error: {}", e),
+            Ok(_) => panic!("the network settings were accepted"),
+        }
+    }
+
+    #[tokio::test]
+    async fn a_dry_run_ends_at_the_first_request() {
+        let llm = LLMProvider::DryRun(Some("gpt-4o-mini".to_string()));
+        let messages = [Message {
+            role: Role::User,
+            content: "Hi".to_string(),
+        }];
+
+        let response = llm.complete(&messages, &GenerationParams::default()).await;
+
+        assert!(matches!(response, Err(LLMError::DryRun)));
+    }
+
+    #[test]
+    fn rejects_an_invalid_proxy() {
+        let message = network_error(GitAIConfig {
+            proxy: Some("http://[::1".to_string()),
+            ..Default::default()
+        });
+
+        assert!(message.starts_with("Invalid proxy 'http://[::1'"), "{}", message);
+    }
+
+    #[test]
+    fn rejects_a_missing_or_invalid_certificate() {
+        let missing = std::env::temp_dir().join("gitai-missing-ca.pem");
+        let message = network_error(GitAIConfig {
+            ca_certs: vec![missing],
+            ..Default::default()
+        });
+        assert!(message.starts_with("Could not read the CA certificate"), "{}", message);
+
+        let invalid = std::env::temp_dir().join(format!("gitai-invalid-{}.pem", std::process::id()));
+        fs::write(&invalid, "-----BEGIN CERTIFICATE-----\nnot base64\n-----END CERTIFICATE-----\n")
+            .unwrap();
+        let message = network_error(GitAIConfig {
+            ca_certs: vec![invalid.clone()],
+            ..Default::default()
+        });
+        fs::remove_file(&invalid).unwrap();
+        assert!(message.starts_with("Invalid CA certificate"), "{}", message);
+    }
+
+    #[test]
+    fn rejects_a_client_certificate_without_its_key() {
+        let message = network_error(GitAIConfig {
+            client_cert: Some("client.pem".into()),
+            ..Default::default()
+        });
+
+        assert_eq!(message, "A client certificate needs both CLIENT_CERT and CLIENT_KEY");
+    }
+
+    #[test]
+    fn compares_model_names_without_their_latest_tag() {
+        assert!(same_model("llama3.2", "llama3.2:latest"));
+        assert!(same_model("gpt-4o-mini", "gpt-4o-mini"));
+        assert!(same_model("localhost:5000/team/coder", "localhost:5000/team/coder:latest"));
+        assert!(!same_model("llama3.2", "llama3.2:1b"));
+        assert!(!same_model("llama3.2:latest", "llama3.2:3b"));
+    }
+
+    #[tokio::test]
+    async fn sends_every_value_of_a_repeated_header() {
+        let (url, requests) = stand_in::serve(vec![(200, "{}")]).await;
+        let config = GitAIConfig {
+            headers: vec![
+                ("X-Team".to_string(), "a".to_string()),
+                ("X-Team".to_string(), "b".to_string()),
+            ],
+            ..Default::default()
+        };
+
+        let client = build_client(&config).unwrap();
+        client.get_json(|client| client.get(&url)).await.unwrap();
+
+        let request = requests.await.unwrap().remove(0).to_lowercase();
+        assert!(request.contains("x-team: a\r\n"));
+        assert!(request.contains("x-team: b\r\n"));
+    }
+
+    #[tokio::test]
+    async fn keeps_the_headers_from_fallbacks_of_another_provider() {
+        let (url, requests) = stand_in::serve(vec![
+            (401, r#"{"error":{"message":"bad gateway key"}}"#),
+            (200, r#"{"message":{"role":"assistant","content":"Hello"},"done":true}"#),
+        ])
+        .await;
+        let config = GitAIConfig {
+            provider: LLMProviderType::OpenaiCompatible,
+            base_url: Some(url.clone()),
+            headers: vec![("X-Gateway-Key".to_string(), "secret".to_string())],
+            fallback: vec![(LLMProviderType::Ollama, None)],
+            ollama_host: Some(url),
+            ..Default::default()
+        };
+        let messages = [Message {
+            role: Role::User,
+            content: "Hi".to_string(),
+        }];
+
+        let llm = get_fallback_chain(&config).unwrap();
+        let response = llm.complete(&messages, &GenerationParams::default()).await;
+
+        assert_eq!(response.unwrap(), "Hello");
+        let requests = requests.await.unwrap();
+        assert!(requests[0].to_lowercase().contains("x-gateway-key: secret"));
+        assert!(!requests[1].to_lowercase().contains("x-gateway-key"));
     }
 }
@@ -0,0 +1,326 @@
+use super::http::{self, HttpClient};
+use super::{
+    sse, usage, GenerationParams, LLMComplete, LLMError, Message, ModelInfo, Role, TextStream,
+};
+use async_trait::async_trait;
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+
+/// Whether an `http://host:port` address has a port, telling it apart from an IPv6 address
+fn has_port(address: &str) -> bool {
+    let authority = address.split_once("://").map_or(address, |(_, rest)| rest);
+    let authority = authority.split('/').next().unwrap_or_default();
+    let host_end = authority.rfind(']').map_or(0, |end| end + 1);
+    authority[host_end..].contains(':')
+}
+
+#[derive(Clone)]
+pub struct OllamaConfig {
+    model: String,
+    api_base_url: String,
+    tags_url: String,
+}
+
+impl OllamaConfig {
+    /// A base URL takes precedence over the host and port
+    pub fn new(
+        host: Option<String>,
+        port: Option<u16>,
+        model: Option<String>,
+        base_url: Option<String>,
+    ) -> Self {
+        let base_url = base_url.unwrap_or_else(|| {
+            let host = host.unwrap_or_else(|| "localhost".to_string());
+            let host = if host.starts_with("http://") || host.starts_with("https://") {
+                host
+            } else {
+                format!("http://{}", host)
+            };
+            // OLLAMA_HOST is often the whole address, as Ollama itself reads it
+            if has_port(&host) {
+                host
+            } else {
+                format!("{}:{}", host, port.unwrap_or(11434))
+            }
+        });
+
+        let base_url = base_url.trim_end_matches('/');
+
+        Self {
+            model: model.unwrap_or_else(|| "llama3.2".to_string()),
+            api_base_url: format!("{}/api/chat", base_url),
+            tags_url: format!("{}/api/tags", base_url),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct OllamaProvider {
+    client: HttpClient,
+    config: OllamaConfig,
+}
+
+impl OllamaProvider {
+    pub fn new(client: HttpClient, config: OllamaConfig) -> Self {
+        Self { client, config }
+    }
+
+    fn build_payload(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+        stream: bool,
+    ) -> Value {
+        let mut payload = json!({
+            "model": self.config.model,
+            "messages": messages.iter().map(|message| {
+                json!({
+                    "role": match message.role {
+                        Role::System => "system",
+                        Role::User => "user",
+                        Role::Assistant => "assistant",
+                    },
+                    "content": message.content,
+                })
+            }).collect::<Vec<Value>>(),
+            "stream": stream,
+        });
+
+        // Sampling settings are model options, where the token limit is `num_predict`
+        let mut options = json!({});
+        if let Some(temperature) = params.temperature {
+            options["temperature"] = json!(temperature);
+        }
+        if let Some(top_p) = params.top_p {
+            options["top_p"] = json!(top_p);
+        }
+        if let Some(max_tokens) = params.max_tokens {
+            options["num_predict"] = json!(max_tokens);
+        }
+        if !params.stop.is_empty() {
+            options["stop"] = json!(params.stop);
+        }
+        if let Some(seed) = params.seed {
+            options["seed"] = json!(seed);
+        }
+        if options != json!({}) {
+            payload["options"] = options;
+        }
+        if let Some(response_schema) = &params.response_schema {
+            payload["format"] = response_schema.schema.clone();
+        }
+
+        payload
+    }
+
+    async fn send(&self, payload: &Value) -> Result<reqwest::Response, LLMError> {
+        let response = self
+            .client
+            .send(|client| client.post(&self.config.api_base_url).json(payload))
+            .await?;
+
+        let status = response.status();
+        match status {
+            StatusCode::OK => Ok(response),
+            // Ollama reports errors as a plain string, e.g. for a model that was not pulled
+            _ => Err(http::response_error(response, http::openai_error).await),
+        }
+    }
+
+    /// The last message, marked `done`, carries the token counts
+    fn record_usage(response_json: &Value) {
+        if response_json.get("done").and_then(Value::as_bool) != Some(true) {
+            return;
+        }
+
+        usage::record(
+            response_json
+                .get("model")
+                .and_then(|model| model.as_str())
+                .unwrap_or("unknown model"),
+            response_json
+                .get("prompt_eval_count")
+                .and_then(Value::as_u64)
+                .unwrap_or_default(),
+            response_json
+                .get("eval_count")
+                .and_then(Value::as_u64)
+                .unwrap_or_default(),
+        );
+    }
+
+    /// A failure after the response started, e.g. a crashed runner, comes as an error line
+    fn parse_delta(line: &str) -> Option<Result<String, LLMError>> {
+        let json_value: Value = serde_json::from_str(line).ok()?;
+        if let Some(error) = json_value.get("error") {
+            let message = error.as_str().map_or_else(|| error.to_string(), String::from);
+            return Some(Err(LLMError::Incomplete(message)));
+        }
+
+        Self::record_usage(&json_value);
+        json_value
+            .get("message")?
+            .get("content")?
+            .as_str()
+            .filter(|content| !content.is_empty())
+            .map(|content| Ok(content.to_string()))
+    }
+
+    async fn complete(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<String, LLMError> {
+        let response = self.send(&self.build_payload(messages, params, false)).await?;
+        let response_json: Value = response.json().await?;
+        Self::record_usage(&response_json);
+        let content = response_json
+            .get("message")
+            .and_then(|message| message.get("content"))
+            .and_then(|content| content.as_str())
+            .ok_or(LLMError::NoCompletionChoice)?;
+        Ok(content.to_string())
+    }
+
+    async fn complete_stream(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<TextStream, LLMError> {
+        let response = self.send(&self.build_payload(messages, params, true)).await?;
+        Ok(sse::line_deltas(response, Self::parse_delta))
+    }
+
+    /// The models pulled locally; their context window is a per model setting left out here
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, LLMError> {
+        let response_json = self
+            .client
+            .get_json(|client| client.get(&self.config.tags_url))
+            .await?;
+
+        let models = response_json
+            .get("models")
+            .and_then(|models| models.as_array())
+            .ok_or(LLMError::UnexpectedResponse)?;
+
+        Ok(models
+            .iter()
+            .filter_map(|model| {
+                Some(ModelInfo {
+                    id: model.get("name")?.as_str()?.to_string(),
+                    context_window: None,
+                })
+            })
+            .collect())
+    }
+}
+
+#[async_trait]
+impl LLMComplete for OllamaProvider {
+    async fn complete(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<String, LLMError> {
+        self.complete(messages, params).await
+    }
+
+    async fn complete_stream(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<TextStream, LLMError> {
+        self.complete_stream(messages, params).await
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, LLMError> {
+        self.list_models().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::stand_in;
+    use super::*;
+    use futures_util::StreamExt;
+
+    fn messages() -> Vec<Message> {
+        vec![Message {
+            role: Role::User,
+            content: "Hi".to_string(),
+        }]
+    }
+
+    fn provider(base_url: String) -> OllamaProvider {
+        let config = OllamaConfig::new(None, None, None, Some(base_url));
+        OllamaProvider::new(stand_in::client(), config)
+    }
+
+    #[test]
+    fn appends_the_port_only_to_a_host_without_one() {
+        let url = |host: &str, port| {
+            OllamaConfig::new(Some(host.to_string()), port, None, None).api_base_url
+        };
+
+        assert_eq!(url("localhost", None), "http://localhost:11434/api/chat");
+        assert_eq!(url("gpu-box", Some(8080)), "http://gpu-box:8080/api/chat");
+        assert_eq!(url("127.0.0.1:11434", None), "http://127.0.0.1:11434/api/chat");
+        assert_eq!(url("http://gpu-box:11434/", Some(1)), "http://gpu-box:11434/api/chat");
+        assert_eq!(url("[::1]", None), "http://[::1]:11434/api/chat");
+        assert_eq!(url("http://[::1]:8080", None), "http://[::1]:8080/api/chat");
+    }
+
+    #[tokio::test]
+    async fn completes_a_chat() {
+        let (url, requests) = stand_in::serve(vec![(
+            200,
+            r#"{"model":"llama3.2","message":{"role":"assistant","content":"Hello"},"done":true}"#,
+        )])
+        .await;
+
+        let response = provider(url).complete(&messages(), &GenerationParams::default()).await;
+
+        assert_eq!(response.unwrap(), "Hello");
+        let request = &requests.await.unwrap()[0];
+        assert!(request.starts_with("POST /api/chat "));
+        assert!(request.contains(r#""model":"llama3.2""#));
+    }
+
+    #[tokio::test]
+    async fn reports_a_missing_model() {
+        let (url, _) = stand_in::serve(vec![(404, r#"{"error":"model 'nope' not found"}"#)]).await;
+
+        let response = provider(url).complete(&messages(), &GenerationParams::default()).await;
+
+        assert!(matches!(response, Err(LLMError::APIError(StatusCode::NOT_FOUND, message))
+            if message == "model 'nope' not found"));
+    }
+
+    #[tokio::test]
+    async fn streams_deltas_until_an_error_line() {
+        let (url, _) = stand_in::serve(vec![(
+            200,
+            concat!(
+                r#"{"message":{"role":"assistant","content":"Hel"},"done":false}"#,
+                "\n",
+                r#"{"message":{"role":"assistant","content":"lo"},"done":false}"#,
+                "\n",
+                r#"{"error":"llama runner process has terminated"}"#,
+                "\n",
+            ),
+        )])
+        .await;
+
+        let stream = provider(url)
+            .complete_stream(&messages(), &GenerationParams::default())
+            .await
+            .unwrap();
+        let deltas: Vec<_> = stream.collect().await;
+
+        assert_eq!(deltas.len(), 3);
+        assert_eq!(deltas[0].as_deref().unwrap(), "Hel");
+        assert_eq!(deltas[1].as_deref().unwrap(), "lo");
+        assert!(matches!(&deltas[2], Err(LLMError::Incomplete(message))
+            if message == "llama runner process has terminated"));
+    }
+}
@@ -1,22 +1,66 @@
-use super::{LLMComplete, LLMError, Message, Role};
+use super::http::{self, HttpClient};
+use super::{
+    sse, usage, GenerationParams, LLMComplete, LLMError, Message, ModelInfo, Role, TextStream,
+};
 use async_trait::async_trait;
 use reqwest::StatusCode;
 use serde_json::{json, Value};
-use thiserror::Error;
+use std::str::FromStr;
+
+/// How the API key is sent to an OpenAI compatible server
+#[derive(Clone, Debug, PartialEq)]
+pub enum AuthScheme {
+    /// `Authorization: Bearer <key>`
+    Bearer,
+    /// The raw key in a custom header, e.g. `header:api-key`
+    Header(String),
+    /// No credentials, for local servers and gateways that authenticate by extra headers
+    None,
+}
+
+impl FromStr for AuthScheme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "bearer" => Ok(AuthScheme::Bearer),
+            "none" => Ok(AuthScheme::None),
+            scheme => match scheme.strip_prefix("header:") {
+                Some(name) if !name.is_empty() => Ok(AuthScheme::Header(name.to_string())),
+                _ => Err(format!(
+                    "Invalid auth scheme '{}', expected bearer, none or header:<name>",
+                    s
+                )),
+            },
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct OpenAIConfig {
-    api_key: String,
+    api_key: Option<String>,
     model: String,
     api_base_url: String,
+    models_url: String,
+    auth_scheme: AuthScheme,
 }
 
 impl OpenAIConfig {
-    pub fn new(api_key: String, model: Option<String>) -> Self {
+    pub fn new(
+        api_key: Option<String>,
+        model: Option<String>,
+        base_url: Option<String>,
+        auth_scheme: AuthScheme,
+    ) -> Self {
+        let base_url = base_url.unwrap_or("https://api.openai.com/v1".to_string());
+        let base_url = base_url.trim_end_matches('/');
+
         OpenAIConfig {
             api_key,
             model: model.unwrap_or("gpt-4o-mini".to_string()),
-            api_base_url: "https://api.openai.com/v1/chat/completions".to_string(),
+            api_base_url: format!("{}/chat/completions", base_url),
+            models_url: format!("{}/models", base_url),
+            auth_scheme,
         }
     }
 }
@@ -24,77 +68,338 @@ impl OpenAIConfig {
 #[derive(Clone)]
 pub struct OpenAIProvider {
     config: OpenAIConfig,
-    client: reqwest::Client,
+    client: HttpClient,
 }
 
-#[derive(Error, Debug)]
-#[error("{0}")]
-pub struct AIPromptError(String);
-
 impl OpenAIProvider {
-    pub fn new(client: reqwest::Client, config: OpenAIConfig) -> Self {
+    pub fn new(client: HttpClient, config: OpenAIConfig) -> Self {
         OpenAIProvider { client, config }
     }
 
-    async fn complete(&self, messages: &[Message]) -> Result<String, LLMError> {
-        let payload = json!({
+    fn build_payload(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+        stream: bool,
+    ) -> Value {
+        let mut payload = json!({
             "model": self.config.model,
             "messages": messages.iter().map(|message| {
                 json!({
                     "role": match message.role {
                         Role::System => "system",
                         Role::User => "user",
+                        Role::Assistant => "assistant",
                     },
                     "content": message.content,
                 })
             }).collect::<Vec<Value>>(),
+            "stream": stream,
         });
 
+        if let Some(temperature) = params.temperature {
+            payload["temperature"] = json!(temperature);
+        }
+        if let Some(top_p) = params.top_p {
+            payload["top_p"] = json!(top_p);
+        }
+        if let Some(max_tokens) = params.max_tokens {
+            payload["max_tokens"] = json!(max_tokens);
+        }
+        if !params.stop.is_empty() {
+            payload["stop"] = json!(params.stop);
+        }
+        if let Some(seed) = params.seed {
+            payload["seed"] = json!(seed);
+        }
+        if let Some(response_schema) = &params.response_schema {
+            payload["response_format"] = json!({
+                "type": "json_schema",
+                "json_schema": {
+                    "name": response_schema.name,
+                    "schema": response_schema.schema,
+                    "strict": true,
+                },
+            });
+        }
+
+        // Without it streams do not report their usage
+        if stream {
+            payload["stream_options"] = json!({ "include_usage": true });
+        }
+
+        payload
+    }
+
+    fn authorize(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match (&self.config.api_key, &self.config.auth_scheme) {
+            (Some(api_key), AuthScheme::Bearer) => {
+                request.header("Authorization", format!("Bearer {}", api_key))
+            }
+            (Some(api_key), AuthScheme::Header(name)) => request.header(name, api_key),
+            _ => request,
+        }
+    }
+
+    async fn send(&self, payload: &Value) -> Result<reqwest::Response, LLMError> {
         let response = self
             .client
-            .post(&self.config.api_base_url)
-            .header("Authorization", format!("Bearer {}", self.config.api_key))
-            .json(&payload)
-            .send()
+            .send(|client| self.authorize(client.post(&self.config.api_base_url).json(payload)))
             .await?;
 
         let status = response.status();
 
         match status {
-            StatusCode::OK => {
-                let response_json: Value = response.json().await?;
-
-                let content = response_json
-                    .get("choices")
-                    .and_then(|choices| choices.get(0))
-                    .and_then(|choice| choice.get("message"))
-                    .and_then(|message| message.get("content"))
-                    .and_then(|content| content.as_str())
-                    .ok_or(LLMError::NoCompletionChoice)?;
-
-                Ok(content.to_string())
-            }
-            _ => {
-                let error_json: Value = response.json().await?;
-
-                let error_message = error_json
-                    .get("error")
-                    .and_then(|error| error.get("message"))
-                    .and_then(|msg| msg.as_str())
-                    .ok_or(LLMError::UnexpectedResponse)?
-                    .into();
+            StatusCode::OK => Ok(response),
+            _ => Err(http::response_error(response, http::openai_error).await),
+        }
+    }
 
-                Err(LLMError::APIError(status, error_message))
+    /// Not every OpenAI compatible server supports structured output, those that reject it are
+    /// asked again without the schema, the prompt asks for JSON anyway
+    async fn send_messages(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+        stream: bool,
+    ) -> Result<reqwest::Response, LLMError> {
+        match self.send(&self.build_payload(messages, params, stream)).await {
+            Err(LLMError::APIError(StatusCode::BAD_REQUEST | StatusCode::UNPROCESSABLE_ENTITY, _))
+                if params.response_schema.is_some() =>
+            {
+                let params = GenerationParams {
+                    response_schema: None,
+                    ..params.clone()
+                };
+                self.send(&self.build_payload(messages, &params, stream)).await
             }
+            result => result,
         }
     }
+
+    fn parse_delta(data: &str, model: &str) -> Option<String> {
+        let json_value: Value = serde_json::from_str(data).ok()?;
+        // The usage comes in a last chunk without choices
+        usage::record_openai(&json_value, model);
+
+        json_value
+            .get("choices")?
+            .get(0)?
+            .get("delta")?
+            .get("content")?
+            .as_str()
+            .map(String::from)
+    }
+
+    async fn complete(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<String, LLMError> {
+        let response = self.send_messages(messages, params, false).await?;
+        let response_json: Value = response.json().await?;
+        usage::record_openai(&response_json, &self.config.model);
+
+        let content = response_json
+            .get("choices")
+            .and_then(|choices| choices.get(0))
+            .and_then(|choice| choice.get("message"))
+            .and_then(|message| message.get("content"))
+            .and_then(|content| content.as_str())
+            .ok_or(LLMError::NoCompletionChoice)?;
+
+        Ok(content.to_string())
+    }
+
+    async fn complete_stream(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<TextStream, LLMError> {
+        let response = self.send_messages(messages, params, true).await?;
+        let model = self.config.model.clone();
+
+        Ok(sse::text_deltas(response, move |data| Self::parse_delta(data, &model)))
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, LLMError> {
+        let response_json = self
+            .client
+            .get_json(|client| self.authorize(client.get(&self.config.models_url)))
+            .await?;
+        parse_models(&response_json)
+    }
+}
+
+/// Parses an OpenAI style model list, `{"data": [{"id": ...}]}`.
+///
+/// OpenAI does not report context windows, Groq, OpenRouter and vLLM do, each in its own field.
+pub fn parse_models(response_json: &Value) -> Result<Vec<ModelInfo>, LLMError> {
+    let models = response_json
+        .get("data")
+        .and_then(|data| data.as_array())
+        .ok_or(LLMError::UnexpectedResponse)?;
+
+    Ok(models
+        .iter()
+        .filter_map(|model| {
+            let context_window = ["context_window", "context_length", "max_model_len"]
+                .iter()
+                .find_map(|field| model.get(field).and_then(Value::as_u64));
+            Some(ModelInfo {
+                id: model.get("id")?.as_str()?.to_string(),
+                context_window,
+            })
+        })
+        .collect())
 }
 
 #[async_trait]
 impl LLMComplete for OpenAIProvider {
-    async fn complete(&self, messages: &[Message]) -> Result<String, LLMError> {
-        self.complete(&messages).await
+    async fn complete(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<String, LLMError> {
+        self.complete(messages, params).await
+    }
+
+    async fn complete_stream(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<TextStream, LLMError> {
+        self.complete_stream(messages, params).await
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, LLMError> {
+        self.list_models().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::stand_in;
+    use super::super::ResponseSchema;
+    use super::*;
+    use futures_util::StreamExt;
+
+    fn messages() -> Vec<Message> {
+        vec![Message {
+            role: Role::User,
+            content: "Hi".to_string(),
+        }]
+    }
+
+    async fn complete_with(status: u16, body: &'static str) -> Result<String, LLMError> {
+        let (url, _) = stand_in::serve(vec![(status, body)]).await;
+        let config = OpenAIConfig::new(None, None, Some(url), AuthScheme::None);
+        OpenAIProvider::new(stand_in::client(), config)
+            .complete(&messages(), &GenerationParams::default())
+            .await
+    }
+
+    #[tokio::test]
+    async fn records_the_usage_of_a_stream_under_the_configured_model() {
+        let (url, _) = stand_in::serve(vec![(
+            200,
+            concat!(
+                r#"data: {"choices":[{"delta":{"content":"Hello"}}]}"#,
+                "\n\n",
+                r#"data: {"choices":[],"usage":{"prompt_tokens":7,"completion_tokens":3}}"#,
+                "\n\n",
+                "data: [DONE]\n\n",
+            ),
+        )])
+        .await;
+        let model = "stand-in-streamed-model".to_string();
+        let config = OpenAIConfig::new(None, Some(model), Some(url), AuthScheme::None);
+
+        let stream = OpenAIProvider::new(stand_in::client(), config)
+            .complete_stream(&messages(), &GenerationParams::default())
+            .await
+            .unwrap();
+        let deltas: Vec<_> = stream.collect().await;
+
+        assert_eq!(deltas.len(), 1);
+        assert_eq!(deltas[0].as_deref().unwrap(), "Hello");
+        assert!(usage::report()
+            .unwrap()
+            .contains("7 input + 3 output tokens with stand-in-streamed-model"));
+    }
+
+    #[tokio::test]
+    async fn asks_again_without_a_rejected_schema() {
+        let (url, requests) = stand_in::serve(vec![
+            (400, r#"{"object":"error","message":"response_format is not supported"}"#),
+            (200, r#"{"choices":[{"message":{"content":"{\"subject\":\"add\"}"}}]}"#),
+        ])
+        .await;
+        let config = OpenAIConfig::new(None, None, Some(url), AuthScheme::None);
+        let params = GenerationParams {
+            response_schema: Some(ResponseSchema {
+                name: "commit_message".to_string(),
+                schema: json!({ "type": "object" }),
+            }),
+            ..Default::default()
+        };
+
+        let response = OpenAIProvider::new(stand_in::client(), config)
+            .complete(&messages(), &params)
+            .await;
+
+        assert_eq!(response.unwrap(), r#"{"subject":"add"}"#);
+        let requests = requests.await.unwrap();
+        assert!(requests[0].contains(r#""response_format""#));
+        assert!(!requests[1].contains(r#""response_format""#));
     }
 
+    #[tokio::test]
+    async fn maps_an_unauthorized_response() {
+        let response = complete_with(401, r#"{"error":{"message":"Incorrect API key"}}"#).await;
+
+        assert!(matches!(response, Err(LLMError::AuthError(message))
+            if message == "Incorrect API key"));
+    }
 
+    #[tokio::test]
+    async fn maps_a_rate_limited_response() {
+        let response = complete_with(429, r#"{"error":{"message":"Slow down"}}"#).await;
+
+        assert!(matches!(response, Err(LLMError::RateLimited(message)) if message == "Slow down"));
+    }
+
+    #[tokio::test]
+    async fn maps_a_server_error() {
+        let response = complete_with(503, r#"{"error":{"message":"Engine overloaded"}}"#).await;
+
+        assert!(
+            matches!(response, Err(LLMError::APIError(StatusCode::SERVICE_UNAVAILABLE, message))
+            if message == "Engine overloaded")
+        );
+    }
+
+    #[tokio::test]
+    async fn keeps_an_html_body_as_the_message() {
+        let response = complete_with(502, "<html><body>Bad Gateway</body></html>\n").await;
+
+        assert!(
+            matches!(response, Err(LLMError::APIError(StatusCode::BAD_GATEWAY, message))
+            if message == "<html><body>Bad Gateway</body></html>")
+        );
+    }
+
+    #[tokio::test]
+    async fn reads_a_vllm_error() {
+        let response = complete_with(
+            400,
+            r#"{"object":"error","message":"maximum context length exceeded","code":400}"#,
+        )
+        .await;
+
+        assert!(
+            matches!(response, Err(LLMError::APIError(StatusCode::BAD_REQUEST, message))
+            if message == "maximum context length exceeded")
+        );
+    }
 }
@@ -1,4 +1,5 @@
-use super::{LLMComplete, LLMError, Message, Role};
+use super::http::{self, HttpClient};
+use super::{sse, GenerationParams, LLMComplete, LLMError, Message, Role, TextStream};
 use async_trait::async_trait;
 use reqwest::{
     header::{HeaderMap, HeaderValue},
@@ -13,22 +14,26 @@ pub struct PhindConfig {
 }
 
 impl PhindConfig {
-    pub fn new(model: Option<String>) -> Self {
+    /// Like the other providers, a base URL is the API root the endpoint path is appended to
+    pub fn new(model: Option<String>, base_url: Option<String>) -> Self {
+        let base_url =
+            base_url.unwrap_or_else(|| "https://https.extension.phind.com".to_string());
+
         Self {
             model: model.unwrap_or_else(|| "Phind-70B".to_string()),
-            api_base_url: "https://https.extension.phind.com/agent/".to_string(),
+            api_base_url: format!("{}/agent/", base_url.trim_end_matches('/')),
         }
     }
 }
 
 #[derive(Clone)]
 pub struct PhindProvider {
-    client: reqwest::Client,
+    client: HttpClient,
     config: PhindConfig,
 }
 
 impl PhindProvider {
-    pub fn new(client: reqwest::Client, config: PhindConfig) -> Self {
+    pub fn new(client: HttpClient, config: PhindConfig) -> Self {
         Self { client, config }
     }
 
@@ -43,6 +48,10 @@ impl PhindProvider {
 
     fn parse_line(line: &str) -> Option<String> {
         let data = line.strip_prefix("data: ")?; // Extract data after "data: " prefix
+        Self::parse_delta(data)
+    }
+
+    fn parse_delta(data: &str) -> Option<String> {
         let json_value: Value = serde_json::from_str(data).ok()?;
 
         json_value
@@ -62,10 +71,14 @@ impl PhindProvider {
             .collect()
     }
 
-    async fn complete(&self, messages: &[Message]) -> Result<String, LLMError> {
-        let user_mesage = messages.get(1).ok_or(LLMError::SomeError)?;
+    fn build_payload(&self, messages: &[Message]) -> Result<Value, LLMError> {
+        let user_mesage = messages
+            .iter()
+            .rev()
+            .find(|message| message.role == Role::User)
+            .ok_or(LLMError::SomeError)?;
 
-        let payload = json!({
+        Ok(json!({
             "additional_extension_context": "",
             "allow_magic_buttons": true,
             "is_vscode_extension": true,
@@ -74,54 +87,83 @@ impl PhindProvider {
                     "role": match message.role {
                         Role::System => "system",
                         Role::User => "user",
+                        Role::Assistant => "assistant",
                     },
                     "content": message.content,
                 })
             }).collect::<Vec<Value>>(),
             "requested_model": self.config.model,
             "user_input": user_mesage.content,
-        });
+        }))
+    }
+
+    async fn send(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<reqwest::Response, LLMError> {
+        // The extension API takes no sampling settings
+        params.warn_unsupported("Phind", &[]);
+        let payload = self.build_payload(messages)?;
 
         let headers = Self::create_headers()?;
         let response = self
             .client
-            .post(&self.config.api_base_url)
-            .headers(headers)
-            .json(&payload)
-            .send()
+            .send(|client| {
+                client
+                    .post(&self.config.api_base_url)
+                    .headers(headers.clone())
+                    .json(&payload)
+            })
             .await?;
 
         let status = response.status();
         match status {
-            StatusCode::OK => {
-                let response_text = response.text().await?;
-                let full_text = Self::parse_stream_response(&response_text);
-
-                if full_text.is_empty() {
-                    return Err(LLMError::NoCompletionChoice);
-                }
-                Ok(full_text)
-            }
-            _ => {
-                let error_text = response.text().await?;
-                let error_json: Value = serde_json::from_str(&error_text)
-                    .unwrap_or_else(|_| json!({"error": {"message": "Unknown error"}}));
-
-                let error_message = error_json
-                    .get("error")
-                    .and_then(|error| error.get("message"))
-                    .and_then(|msg| msg.as_str())
-                    .ok_or(LLMError::UnexpectedResponse)?
-                    .into();
-                Err(LLMError::APIError(status, error_message))
-            }
+            StatusCode::OK => Ok(response),
+            _ => Err(http::response_error(response, http::openai_error).await),
         }
     }
+
+    async fn complete(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<String, LLMError> {
+        let response = self.send(messages, params).await?;
+        let response_text = response.text().await?;
+        let full_text = Self::parse_stream_response(&response_text);
+
+        if full_text.is_empty() {
+            return Err(LLMError::NoCompletionChoice);
+        }
+        Ok(full_text)
+    }
+
+    async fn complete_stream(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<TextStream, LLMError> {
+        let response = self.send(messages, params).await?;
+        Ok(sse::text_deltas(response, Self::parse_delta))
+    }
 }
 
 #[async_trait]
 impl LLMComplete for PhindProvider {
-    async fn complete(&self, messages: &[Message]) -> Result<String, LLMError> {
-        self.complete(&messages).await
+    async fn complete(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<String, LLMError> {
+        self.complete(messages, params).await
+    }
+
+    async fn complete_stream(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<TextStream, LLMError> {
+        self.complete_stream(messages, params).await
     }
 }
@@ -0,0 +1,275 @@
+use super::cache::fnv1a_128;
+use super::{
+    GenerationParams, LLMComplete, LLMError, LLMProvider, Message, ModelInfo, Role, TextStream,
+};
+use async_trait::async_trait;
+use futures_util::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[derive(Serialize, Deserialize)]
+struct RecordedMessage {
+    role: String,
+    content: String,
+}
+
+/// A request and the response it got; the request is only kept to be read by people
+#[derive(Serialize, Deserialize)]
+struct FixtureEntry {
+    request: Vec<RecordedMessage>,
+    response: String,
+}
+
+/// Recorded exchanges by request key, sorted so that fixtures diff well
+type Fixture = BTreeMap<String, FixtureEntry>;
+
+/// Versions the key layout, fixtures are committed and must keep replaying when the cache's
+/// keys change
+const KEY_VERSION: &str = "fixture-v1";
+
+/// The key of a request in a fixture, without the provider, so a recording replays whichever
+/// provider made it
+fn request_key(messages: &[Message], params: &GenerationParams) -> String {
+    let params = serde_json::to_string(params).unwrap_or_default();
+    let mut parts: Vec<&[u8]> = vec![KEY_VERSION.as_bytes(), params.as_bytes()];
+    for message in messages {
+        parts.push(match message.role {
+            Role::System => b"system",
+            Role::User => b"user",
+            Role::Assistant => b"assistant",
+        });
+        parts.push(message.content.as_bytes());
+    }
+
+    format!("{:032x}", fnv1a_128(&parts))
+}
+
+fn load_fixture(path: &Path) -> Result<Fixture, String> {
+    let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    serde_json::from_str(&content).map_err(|e| e.to_string())
+}
+
+/// Serves the responses recorded in a fixture file, without any network
+#[derive(Clone)]
+pub struct ReplayProvider {
+    path: PathBuf,
+    fixture: Arc<Fixture>,
+}
+
+impl ReplayProvider {
+    pub fn new(path: PathBuf) -> Result<Self, String> {
+        let fixture = load_fixture(&path)
+            .map_err(|e| format!("Could not read the fixture {}: {}", path.display(), e))?;
+
+        Ok(Self {
+            path,
+            fixture: Arc::new(fixture),
+        })
+    }
+
+    async fn complete(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<String, LLMError> {
+        let key = request_key(messages, params);
+        match self.fixture.get(&key) {
+            Some(entry) => Ok(entry.response.clone()),
+            None => Err(LLMError::InvalidRequest(format!(
+                "no response for request {} in {}, record it again with --record",
+                key,
+                self.path.display()
+            ))),
+        }
+    }
+
+    async fn complete_stream(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<TextStream, LLMError> {
+        let response = self.complete(messages, params).await?;
+        Ok(stream::once(async move { Ok(response) }).boxed())
+    }
+}
+
+#[async_trait]
+impl LLMComplete for ReplayProvider {
+    async fn complete(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<String, LLMError> {
+        self.complete(messages, params).await
+    }
+
+    async fn complete_stream(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<TextStream, LLMError> {
+        self.complete_stream(messages, params).await
+    }
+}
+
+/// A provider whose exchanges are added to a fixture file for the replay provider
+#[derive(Clone)]
+pub struct RecordingProvider {
+    inner: Box<LLMProvider>,
+    path: PathBuf,
+}
+
+struct RecordingStream {
+    stream: TextStream,
+    text: String,
+    path: PathBuf,
+    key: String,
+    request: Vec<RecordedMessage>,
+}
+
+/// Adds an exchange to the fixture, which is created on the first one.
+///
+/// A failure to record does not fail the command, the response is still good.
+fn record(path: &Path, key: String, request: Vec<RecordedMessage>, response: String) {
+    let mut fixture = if path.exists() {
+        match load_fixture(path) {
+            Ok(fixture) => fixture,
+            Err(e) => {
+                eprintln!("Could not record the response in {}: {}", path.display(), e);
+                return;
+            }
+        }
+    } else {
+        Fixture::new()
+    };
+    fixture.insert(key, FixtureEntry { request, response });
+
+    let result = serde_json::to_string_pretty(&fixture)
+        .map_err(|e| e.to_string())
+        .and_then(|content| fs::write(path, content + "\n").map_err(|e| e.to_string()));
+    if let Err(e) = result {
+        eprintln!("Could not record the response in {}: {}", path.display(), e);
+    }
+}
+
+fn recorded_request(messages: &[Message]) -> Vec<RecordedMessage> {
+    messages
+        .iter()
+        .map(|message| RecordedMessage {
+            role: match message.role {
+                Role::System => "system",
+                Role::User => "user",
+                Role::Assistant => "assistant",
+            }
+            .to_string(),
+            content: message.content.clone(),
+        })
+        .collect()
+}
+
+impl RecordingProvider {
+    pub fn new(inner: LLMProvider, path: PathBuf) -> Self {
+        Self {
+            inner: Box::new(inner),
+            path,
+        }
+    }
+
+    async fn complete(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<String, LLMError> {
+        let response = self.inner.complete(messages, params).await?;
+        record(
+            &self.path,
+            request_key(messages, params),
+            recorded_request(messages),
+            response.clone(),
+        );
+        Ok(response)
+    }
+
+    /// Records the streamed text once the stream ends, a stream that fails is not recorded
+    async fn complete_stream(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<TextStream, LLMError> {
+        let state = RecordingStream {
+            stream: self.inner.complete_stream(messages, params).await?,
+            text: String::new(),
+            path: self.path.clone(),
+            key: request_key(messages, params),
+            request: recorded_request(messages),
+        };
+
+        Ok(stream::unfold(Some(state), |state| async move {
+            let mut state = state?;
+            match state.stream.next().await {
+                Some(Ok(delta)) => {
+                    state.text.push_str(&delta);
+                    Some((Ok(delta), Some(state)))
+                }
+                Some(Err(e)) => Some((Err(e), None)),
+                None => {
+                    record(&state.path, state.key, state.request, state.text);
+                    None
+                }
+            }
+        })
+        .boxed())
+    }
+}
+
+#[async_trait]
+impl LLMComplete for RecordingProvider {
+    async fn complete(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<String, LLMError> {
+        self.complete(messages, params).await
+    }
+
+    async fn complete_stream(
+        &self,
+        messages: &[Message],
+        params: &GenerationParams,
+    ) -> Result<TextStream, LLMError> {
+        self.complete_stream(messages, params).await
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, LLMError> {
+        self.inner.list_models().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_key_of_recorded_requests() {
+        let messages = [
+            Message {
+                role: Role::System,
+                content: "You write commit messages".to_string(),
+            },
+            Message {
+                role: Role::User,
+                content: "Describe this diff".to_string(),
+            },
+        ];
+        let params = GenerationParams {
+            temperature: Some(0.2),
+            ..Default::default()
+        };
+
+        // A different key would no longer find the responses of committed fixtures
+        assert_eq!(request_key(&messages, &params), "9de225d405338244a69d2980e1e8a215");
+    }
+}
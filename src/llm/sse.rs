@@ -0,0 +1,158 @@
+use super::http::transport_error;
+use super::{LLMError, TextStream};
+use futures_util::future;
+use futures_util::stream::{self, BoxStream, Stream, StreamExt};
+
+struct SseState {
+    body: BoxStream<'static, reqwest::Result<Vec<u8>>>,
+    buffer: Vec<u8>,
+    done: bool,
+}
+
+/// Splits a streamed response body into lines, without the trailing line break
+pub fn lines(response: reqwest::Response) -> impl Stream<Item = Result<String, LLMError>> {
+    body_lines(
+        response
+            .bytes_stream()
+            .map(|chunk| chunk.map(|bytes| bytes.to_vec()))
+            .boxed(),
+    )
+}
+
+/// Chunks end anywhere, even inside a line or a UTF-8 character, so lines are cut from the
+/// bytes and only decoded once whole
+fn body_lines(
+    body: BoxStream<'static, reqwest::Result<Vec<u8>>>,
+) -> impl Stream<Item = Result<String, LLMError>> {
+    let state = SseState {
+        body,
+        buffer: Vec::new(),
+        done: false,
+    };
+
+    stream::unfold(state, |mut state| async move {
+        loop {
+            if let Some(position) = state.buffer.iter().position(|&byte| byte == b'\n') {
+                let line: Vec<u8> = state.buffer.drain(..=position).collect();
+                let line = String::from_utf8_lossy(&line).trim_end().to_string();
+                return Some((Ok(line), state));
+            }
+
+            if state.done {
+                if state.buffer.is_empty() {
+                    return None;
+                }
+                let line = String::from_utf8_lossy(&state.buffer)
+                    .trim_end()
+                    .to_string();
+                state.buffer.clear();
+                return Some((Ok(line), state));
+            }
+
+            match state.body.next().await {
+                Some(Ok(chunk)) => state.buffer.extend_from_slice(&chunk),
+                Some(Err(e)) => {
+                    state.done = true;
+                    state.buffer.clear();
+                    return Some((Err(transport_error(e)), state));
+                }
+                None => state.done = true,
+            }
+        }
+    })
+}
+
+/// The payloads of the `data:` fields of a server-sent events response
+pub fn data_events(response: reqwest::Response) -> impl Stream<Item = Result<String, LLMError>> {
+    line_data(lines(response))
+}
+
+fn line_data(
+    lines: impl Stream<Item = Result<String, LLMError>>,
+) -> impl Stream<Item = Result<String, LLMError>> {
+    lines.filter_map(|line| async move {
+        match line {
+            Ok(line) => line
+                .strip_prefix("data:")
+                .map(|data| Ok(data.trim_start().to_string())),
+            Err(e) => Some(Err(e)),
+        }
+    })
+}
+
+/// Maps every `data:` payload to its text delta, skipping the events that carry none
+pub fn text_deltas<F>(response: reqwest::Response, mut parse_delta: F) -> TextStream
+where
+    F: FnMut(&str) -> Option<String> + Send + 'static,
+{
+    data_events(response)
+        .filter_map(move |event| {
+            future::ready(match event {
+                Ok(data) => parse_delta(&data).map(Ok),
+                Err(e) => Some(Err(e)),
+            })
+        })
+        .boxed()
+}
+
+/// Maps every line of a newline delimited JSON response to its text delta, or to the error
+/// the line reports
+pub fn line_deltas<F>(response: reqwest::Response, mut parse_delta: F) -> TextStream
+where
+    F: FnMut(&str) -> Option<Result<String, LLMError>> + Send + 'static,
+{
+    lines(response)
+        .filter_map(move |line| {
+            future::ready(match line {
+                Ok(line) => parse_delta(&line),
+                Err(e) => Some(Err(e)),
+            })
+        })
+        .boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The lines of a body that arrives in `chunks`
+    async fn split_lines(chunks: &[&[u8]]) -> Vec<String> {
+        let chunks = chunks
+            .iter()
+            .map(|chunk| Ok(chunk.to_vec()))
+            .collect::<Vec<reqwest::Result<Vec<u8>>>>();
+
+        body_lines(stream::iter(chunks).boxed())
+            .map(Result::unwrap)
+            .collect()
+            .await
+    }
+
+    #[tokio::test]
+    async fn joins_an_event_split_across_chunks() {
+        let lines = split_lines(&[b"data: {\"te", b"xt\":\"Hi\"}\r", b"\n\ndata: [DO", b"NE]"]).await;
+
+        assert_eq!(lines, ["data: {\"text\":\"Hi\"}", "", "data: [DONE]"]);
+    }
+
+    #[tokio::test]
+    async fn joins_a_character_split_across_chunks() {
+        let text = "data: caf\u{e9} \u{1f680}\n".as_bytes();
+        let (first, rest) = text.split_at(10);
+        let (second, third) = rest.split_at(3);
+
+        assert_eq!(split_lines(&[first, second, third]).await, ["data: café 🚀"]);
+    }
+
+    #[tokio::test]
+    async fn keeps_only_the_data_of_events() {
+        let lines = stream::iter(
+            ["event: delta", "data:{\"a\":1}", ": keep-alive", "data: [DONE]", ""]
+                .map(|line| Ok(line.to_string())),
+        );
+
+        let data = line_data(lines).map(Result::unwrap).collect::<Vec<_>>().await;
+
+        assert_eq!(data, ["{\"a\":1}", "[DONE]"]);
+    }
+}
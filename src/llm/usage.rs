@@ -0,0 +1,175 @@
+use super::{GenerationParams, Message, Role};
+use serde_json::Value;
+use std::sync::Mutex;
+
+/// Prices in USD per million input and output tokens, matched by the longest model prefix
+const PRICES: [(&str, f64, f64); 22] = [
+    ("gpt-4o-mini", 0.15, 0.60),
+    ("gpt-4o", 2.50, 10.00),
+    ("gpt-4.1-nano", 0.10, 0.40),
+    ("gpt-4.1-mini", 0.40, 1.60),
+    ("gpt-4.1", 2.00, 8.00),
+    ("o3-mini", 1.10, 4.40),
+    ("o4-mini", 1.10, 4.40),
+    ("claude-3-haiku", 0.25, 1.25),
+    ("claude-3-5-haiku", 0.80, 4.00),
+    ("claude-3-5-sonnet", 3.00, 15.00),
+    ("claude-3-7-sonnet", 3.00, 15.00),
+    ("claude-sonnet-4", 3.00, 15.00),
+    ("claude-3-opus", 15.00, 75.00),
+    ("claude-opus-4", 15.00, 75.00),
+    ("gemini-1.5-flash", 0.075, 0.30),
+    ("gemini-1.5-pro", 1.25, 5.00),
+    ("gemini-2.0-flash", 0.10, 0.40),
+    ("gemini-2.5-flash", 0.30, 2.50),
+    ("gemini-2.5-pro", 1.25, 10.00),
+    ("mixtral-8x7b-32768", 0.24, 0.24),
+    ("llama-3.3-70b-versatile", 0.59, 0.79),
+    ("llama-3.1-8b-instant", 0.05, 0.08),
+];
+
+/// Tokens used by the requests of this run, per model
+static USAGE: Mutex<Vec<(String, TokenUsage)>> = Mutex::new(Vec::new());
+
+#[derive(Clone, Copy, Default)]
+pub struct TokenUsage {
+    pub input: u64,
+    pub output: u64,
+}
+
+pub struct ModelPrice {
+    pub input: f64,
+    pub output: f64,
+}
+
+pub fn price_for(model: &str) -> Option<ModelPrice> {
+    // Gateways prefix the model with its vendor, e.g. `openai/gpt-4o-mini`, while dated names
+    // such as `gpt-4o-mini-2024-07-18` still match by prefix
+    let model = model.rsplit('/').next().unwrap_or(model);
+
+    PRICES
+        .iter()
+        .filter(|(prefix, _, _)| model.starts_with(prefix))
+        .max_by_key(|(prefix, _, _)| prefix.len())
+        .map(|(_, input, output)| ModelPrice {
+            input: *input,
+            output: *output,
+        })
+}
+
+pub fn cost(model: &str, usage: TokenUsage) -> Option<f64> {
+    let price = price_for(model)?;
+    Some((usage.input as f64 * price.input + usage.output as f64 * price.output) / 1_000_000.0)
+}
+
+/// About four characters per token for English text and code, plus the per message framing
+pub fn estimate_tokens(messages: &[Message]) -> u64 {
+    messages
+        .iter()
+        .map(|message| message.content.chars().count().div_ceil(4) as u64 + 4)
+        .sum()
+}
+
+/// Prints the messages exactly as they would be sent, with their estimated size and cost
+pub fn print_dry_run(messages: &[Message], params: &GenerationParams, model: Option<&str>) {
+    for message in messages {
+        let role = match message.role {
+            Role::System => "system",
+            Role::User => "user",
+            Role::Assistant => "assistant",
+        };
+        println!("----- {} -----\n{}\n", role, message.content);
+    }
+
+    if *params != GenerationParams::default() {
+        println!(
+            "----- parameters -----\n{}\n",
+            serde_json::to_string(params).unwrap_or_default()
+        );
+    }
+
+    let input = estimate_tokens(messages);
+    let estimate = match model {
+        Some(model) => match cost(model, TokenUsage { input, output: 0 }) {
+            Some(cost) => format!(
+                "~{} input tokens, ~${:.4} for the input with {}",
+                input, cost, model
+            ),
+            None => format!("~{} input tokens, no known price for {}", input, model),
+        },
+        None => format!("~{} input tokens, pass --model to estimate the cost", input),
+    };
+    println!("----- estimate -----\n{}", estimate);
+}
+
+pub fn record(model: &str, input: u64, output: u64) {
+    let Ok(mut usage) = USAGE.lock() else {
+        return;
+    };
+
+    match usage.iter_mut().find(|(name, _)| name == model) {
+        Some((_, total)) => {
+            total.input += input;
+            total.output += output;
+        }
+        None => usage.push((model.to_string(), TokenUsage { input, output })),
+    }
+}
+
+/// Records the `usage` object of OpenAI style responses and stream chunks.
+///
+/// Groq reports the usage of streams in `x_groq.usage` instead.
+pub fn record_openai(response_json: &Value, fallback_model: &str) {
+    let Some(usage) = response_json
+        .get("usage")
+        .filter(|usage| !usage.is_null())
+        .or_else(|| {
+            response_json
+                .get("x_groq")
+                .and_then(|x_groq| x_groq.get("usage"))
+        })
+    else {
+        return;
+    };
+
+    let model = response_json
+        .get("model")
+        .and_then(|model| model.as_str())
+        .unwrap_or(fallback_model);
+
+    record(
+        model,
+        usage
+            .get("prompt_tokens")
+            .and_then(Value::as_u64)
+            .unwrap_or_default(),
+        usage
+            .get("completion_tokens")
+            .and_then(Value::as_u64)
+            .unwrap_or_default(),
+    );
+}
+
+/// One line per model used in this run, with the cost when the price is known
+pub fn report() -> Option<String> {
+    let usage = USAGE.lock().ok()?;
+    if usage.is_empty() {
+        return None;
+    }
+
+    Some(
+        usage
+            .iter()
+            .map(|(model, usage)| {
+                let cost = cost(model, *usage)
+                    .map(|cost| format!(", ${:.4}", cost))
+                    .unwrap_or_default();
+                format!(
+                    "Usage: {} input + {} output tokens with {}{}",
+                    usage.input, usage.output, model, cost
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+    )
+}
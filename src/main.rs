@@ -2,14 +2,18 @@ use clap::Parser;
 use std::process;
 
 use cli::{Cli, Commands};
-use command::{CommandType, GitAICommand};
+use command::{
+    BranchSource, CommandType, Convention, GitAICommand, LintTarget, SquashSource,
+    DEFAULT_BRANCH_PATTERN,
+};
 use commit_reference::CommitReference;
 use config::GitAIConfig;
 use error::GitAIError;
 use git_entity::commit::Commit;
 use git_entity::diff::Diff;
+use git_entity::log::LogQuery;
 use git_entity::GitEntity;
-use llm::get_llm;
+use llm::{get_cache, LLMError};
 mod cli;
 mod command;
 mod commit_reference;
@@ -21,9 +25,19 @@ mod util;
 
 #[tokio::main]
 async fn main() {
-    if let Err(e) = run().await {
-        eprintln!("\x1b[91m\rerror:\x1b[0m {e}");
-        process::exit(1);
+    let result = run().await;
+
+    if let Some(report) = llm::usage::report() {
+        eprintln!("{}", report);
+    }
+
+    match result {
+        // The dry run printed the request in place of the response
+        Ok(()) | Err(GitAIError::LLMError(LLMError::DryRun)) => {}
+        Err(e) => {
+            eprintln!("\x1b[91m\rerror:\x1b[0m {e}");
+            process::exit(1);
+        }
     }
 }
 
@@ -35,8 +49,7 @@ async fn run() -> Result<(), GitAIError> {
         Err(e) => return Err(e),
     };
 
-    let llm = get_llm(config.provider, config.model, config.api_key).unwrap();
-    let command = GitAICommand::new(llm);
+    let command = GitAICommand::new(config.clone());
 
     match cli.command {
         Commands::Generate => {
@@ -73,7 +86,190 @@ async fn run() -> Result<(), GitAIError> {
             Ok(())
         }
         Commands::Configure => {
-            command.execute(CommandType::Configure).await?;
+            command
+                .execute(CommandType::Configure {
+                    config: Box::new(config),
+                })
+                .await?;
+            Ok(())
+        }
+        Commands::Branch {
+            description,
+            from_diff,
+            staged,
+            ticket,
+            count,
+        } => {
+            let source = if from_diff {
+                BranchSource::Changes(GitEntity::Diff(Diff::from_working_tree(staged)?))
+            } else if let Some(description) = description {
+                BranchSource::Description(description)
+            } else {
+                return Err(GitAIError::InvalidArguments(
+                    "`branch` expects a description or --from-diff to be present".into(),
+                ));
+            };
+
+            let pattern = config
+                .branch_pattern
+                .unwrap_or_else(|| DEFAULT_BRANCH_PATTERN.to_string());
+
+            command
+                .execute(CommandType::Branch {
+                    source,
+                    ticket,
+                    pattern,
+                    count,
+                })
+                .await?;
+
+            Ok(())
+        }
+        Commands::Reword { reference, force } => {
+            let (from, to) = match reference {
+                CommitReference::Single(from) => (from, "HEAD".to_string()),
+                CommitReference::Range { from, to } => (from, to),
+                CommitReference::TripleDots { .. } => {
+                    return Err(GitAIError::InvalidArguments(
+                        "`reword` expects a <from>..<to> range".into(),
+                    ));
+                }
+            };
+
+            command
+                .execute(CommandType::Reword { from, to, force })
+                .await?;
+
+            Ok(())
+        }
+        Commands::Conflicts => {
+            command.execute(CommandType::Conflicts).await?;
+            Ok(())
+        }
+        Commands::SquashMessage { reference, editor } => {
+            let source = match (reference, editor) {
+                (_, Some(path)) => SquashSource::EditorFile(path),
+                (Some(CommitReference::Single(from)), None) => SquashSource::Range {
+                    from,
+                    to: "HEAD".to_string(),
+                },
+                (Some(CommitReference::Range { from, to }), None) => {
+                    SquashSource::Range { from, to }
+                }
+                _ => {
+                    return Err(GitAIError::InvalidArguments(
+                        "`squash-message` expects a <from>..<to> range or --editor to be present"
+                            .into(),
+                    ));
+                }
+            };
+
+            command
+                .execute(CommandType::SquashMessage { source })
+                .await?;
+
+            Ok(())
+        }
+        Commands::Ask {
+            question,
+            paths,
+            since,
+            until,
+            author,
+        } => {
+            let constraints = LogQuery {
+                author,
+                since,
+                until,
+                paths,
+                ..Default::default()
+            };
+
+            command
+                .execute(CommandType::Ask {
+                    question,
+                    constraints,
+                })
+                .await?;
+
+            Ok(())
+        }
+        Commands::Do { intent } => {
+            command.execute(CommandType::Do { intent }).await?;
+            Ok(())
+        }
+        Commands::Standup {
+            since,
+            author,
+            mut repos,
+        } => {
+            if repos.is_empty() {
+                repos.push(".".into());
+            }
+
+            command
+                .execute(CommandType::Standup {
+                    since,
+                    author,
+                    repos,
+                })
+                .await?;
+
+            Ok(())
+        }
+        Commands::LintMessage {
+            file,
+            commit,
+            check_diff,
+            install_hook,
+        } => {
+            let target = if install_hook {
+                LintTarget::InstallHook
+            } else if let Some(file) = file {
+                LintTarget::File(file)
+            } else if let Some(commit) = commit {
+                LintTarget::Commit(commit)
+            } else {
+                return Err(GitAIError::InvalidArguments(
+                    "`lint-message` expects a message file, --commit or --install-hook to be present"
+                        .into(),
+                ));
+            };
+
+            let convention = Convention::from_config(config.commit_convention.as_deref())?;
+
+            command
+                .execute(CommandType::LintMessage {
+                    target,
+                    convention,
+                    check_diff,
+                })
+                .await?;
+
+            Ok(())
+        }
+        Commands::VersionBump { tag } => {
+            command
+                .execute(CommandType::VersionBump { create_tag: tag })
+                .await?;
+            Ok(())
+        }
+        Commands::Cache { action } => {
+            command
+                .execute(CommandType::Cache {
+                    action,
+                    cache: get_cache(&config)?,
+                })
+                .await?;
+            Ok(())
+        }
+        Commands::Models => {
+            command
+                .execute(CommandType::Models {
+                    provider: config.provider,
+                    model: config.model,
+                })
+                .await?;
             Ok(())
         }
     }
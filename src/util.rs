@@ -1,7 +1,171 @@
 use crate::error::GitAIError;
+use serde::de::DeserializeOwned;
 use termimad::print_text;
 
 pub fn print_markdown(content: String) -> Result<(), GitAIError> {
     print_text(&content);
     Ok(())
 }
+
+/// Renders markdown as it streams in, one block at a time.
+///
+/// A block is printed once a blank line outside a code fence ends it, so
+/// lists and code blocks are never rendered half written.
+#[derive(Default)]
+pub struct MarkdownStream {
+    pending: String,
+    scanned: usize,
+    in_code_block: bool,
+}
+
+impl MarkdownStream {
+    pub fn push(&mut self, delta: &str) {
+        for block in self.complete_blocks(delta) {
+            print_text(&block);
+        }
+    }
+
+    /// Prints whatever is left once the stream has ended
+    pub fn finish(&mut self) {
+        if let Some(rest) = self.take_rest() {
+            print_text(&rest);
+        }
+    }
+
+    /// Adds `delta` and returns the blocks it completes
+    fn complete_blocks(&mut self, delta: &str) -> Vec<String> {
+        self.pending.push_str(delta);
+
+        let mut blocks = Vec::new();
+        while let Some(offset) = self.pending[self.scanned..].find('\n') {
+            let end = self.scanned + offset + 1;
+            let line = self.pending[self.scanned..end].trim();
+            self.scanned = end;
+
+            if line.starts_with("```") {
+                self.in_code_block = !self.in_code_block;
+            } else if line.is_empty() && !self.in_code_block {
+                blocks.push(self.pending.drain(..end).collect());
+                self.scanned = 0;
+            }
+        }
+        blocks
+    }
+
+    fn take_rest(&mut self) -> Option<String> {
+        let rest = std::mem::take(&mut self.pending);
+        self.scanned = 0;
+        self.in_code_block = false;
+        (!rest.trim().is_empty()).then_some(rest)
+    }
+}
+
+/// The end of the JSON object or array starting at `start`, skipping brackets inside strings
+fn value_end(text: &str, start: usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (offset, c) in text[start..].char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '{' | '[' if !in_string => depth += 1,
+            '}' | ']' if !in_string => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(start + offset + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The first JSON object or array in `text` that deserializes to `T`, ignoring whatever
+/// surrounds it, e.g. "Here is your commit message:" or a code fence
+pub fn extract_json<T: DeserializeOwned>(text: &str) -> Option<T> {
+    extract_json_where(text, |_| true)
+}
+
+/// Like [`extract_json`], skipping the values `accept` rejects, such as an example object the
+/// model wrote before its answer
+pub fn extract_json_where<T, F>(text: &str, accept: F) -> Option<T>
+where
+    T: DeserializeOwned,
+    F: Fn(&T) -> bool,
+{
+    text.match_indices(['{', '[']).find_map(|(start, _)| {
+        let end = value_end(text, start)?;
+        serde_json::from_str(&text[start..end])
+            .ok()
+            .filter(|value| accept(value))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    /// The blocks printed for a text streamed as `deltas`, the rest included
+    fn stream_blocks(deltas: &[&str]) -> Vec<String> {
+        let mut stream = MarkdownStream::default();
+        let mut blocks = deltas
+            .iter()
+            .flat_map(|delta| stream.complete_blocks(delta))
+            .collect::<Vec<_>>();
+        blocks.extend(stream.take_rest());
+        blocks
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Subject {
+        subject: String,
+    }
+
+    #[test]
+    fn extracts_the_first_json_value_around_prose_and_fences() {
+        let text = "Here it is:\n```json\n{\"subject\": \"add {braces} and \\\"quotes\\\"\"}\n```";
+
+        assert_eq!(
+            extract_json::<Subject>(text).unwrap().subject,
+            r#"add {braces} and "quotes""#
+        );
+        assert_eq!(extract_json::<Vec<u32>>("Numbers: [1, 2] and [3]").unwrap(), [1, 2]);
+        assert_eq!(extract_json::<Subject>("No JSON { here"), None);
+    }
+
+    #[test]
+    fn skips_values_that_do_not_deserialize_or_are_rejected() {
+        let text = r#"For example {"type": "feat"} or {"subject": ""}, so: {"subject": "add"}"#;
+
+        let subject = extract_json_where(text, |value: &Subject| !value.subject.is_empty());
+
+        assert_eq!(subject.unwrap().subject, "add");
+        assert_eq!(extract_json::<Subject>(text).unwrap().subject, "");
+    }
+
+    #[test]
+    fn prints_a_block_once_a_blank_line_ends_it() {
+        let blocks = stream_blocks(&["# Ti", "tle\n", "\nSome ", "text\n\n- a\n- b"]);
+
+        assert_eq!(blocks, ["# Title\n\n", "Some text\n\n", "- a\n- b"]);
+    }
+
+    #[test]
+    fn keeps_a_code_block_whole_across_blank_lines() {
+        let blocks = stream_blocks(&["```rust\nfn a() {}\n", "\nfn b() {}\n``", "`\n\nDone.\n"]);
+
+        assert_eq!(blocks, ["```rust\nfn a() {}\n\nfn b() {}\n```\n\n", "Done.\n"]);
+    }
+
+    #[test]
+    fn keeps_non_ascii_text_across_deltas() {
+        let blocks = stream_blocks(&["Café ", "🚀\n", "\nrésumé"]);
+
+        assert_eq!(blocks, ["Café 🚀\n\n", "résumé"]);
+    }
+}
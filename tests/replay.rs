@@ -0,0 +1,57 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// An empty repository with one staged file, and a home without any gitai config
+fn staged_repository(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("gitai-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("home")).unwrap();
+    fs::create_dir_all(dir.join("repo")).unwrap();
+
+    let repo = dir.join("repo");
+    git(&repo, &["init", "-q"]);
+    fs::write(repo.join("greeting.txt"), "Hello\n").unwrap();
+    git(&repo, &["add", "greeting.txt"]);
+    dir
+}
+
+fn git(repo: &Path, args: &[&str]) {
+    let status = Command::new("git").arg("-C").arg(repo).args(args).status().unwrap();
+    assert!(status.success(), "git {:?} failed", args);
+}
+
+fn gitai(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_gitai"))
+        .args(args)
+        .current_dir(dir.join("repo"))
+        .env("HOME", dir.join("home"))
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn replays_a_recorded_commit_message() {
+    let dir = staged_repository("replay");
+    let fixture = dir.join("generate.json");
+    let fixture = fixture.to_str().unwrap();
+
+    let recorded = gitai(
+        &dir,
+        &[
+            "--exec",
+            r#"cat >/dev/null; echo '{"type":"feat","subject":"add a greeting"}'"#,
+            "--record",
+            fixture,
+            "generate",
+        ],
+    );
+    let replayed = gitai(&dir, &["--replay", fixture, "generate"]);
+
+    let stdout = String::from_utf8_lossy(&replayed.stdout);
+    assert!(replayed.status.success(), "{}", String::from_utf8_lossy(&replayed.stderr));
+    assert!(stdout.contains("Commit message: feat: add a greeting"), "{}", stdout);
+    assert_eq!(recorded.stdout, replayed.stdout);
+
+    fs::remove_dir_all(&dir).unwrap();
+}